@@ -0,0 +1,98 @@
+//! Measures what byte-encoded chunks gain over the `(OpCode, line)` pairs
+//! they replaced: the same instructions take about a ninth of the memory.
+//! Fetching is timed too, but only to show it has not regressed: results
+//! vary from run to run, and faster execution is not claimed here.
+//!
+//! Run with `cargo bench`.
+
+use std::hint::black_box;
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+
+use rlox::compiler::Compiler;
+use rlox::heap::Heap;
+use rlox::program::{Chunk, OpCode};
+use rlox::tokenizer::Tokenizer;
+use rlox::value::{Function, Object, Value};
+
+const RUNS: usize = 5;
+const FETCH_PASSES: usize = 20;
+
+fn compile(source: &str, heap: &mut Heap) -> Function {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut compiler = Compiler::new(&chars, Tokenizer::new(&chars), heap);
+    let script = compiler.compile();
+    assert!(!compiler.had_error, "benchmark program failed to compile");
+    script
+}
+
+/// The fastest of `RUNS` runs.
+fn time(mut f: impl FnMut()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+/// A long function body, so the instruction stream outgrows the caches.
+fn large_program() -> String {
+    let mut source = String::from("fun f(a, b, c) {\n");
+    for i in 0..100_000 {
+        source.push_str(&format!(
+            "  a = a + b * {i} - c;\n  if (a > b) c = c % 3;\n"
+        ));
+    }
+    source.push_str("}\n");
+    source
+}
+
+fn fetch_chunk(chunk: &Chunk) {
+    let mut offset = 0;
+    while offset < chunk.len() {
+        let (op, next) = chunk.decode(offset);
+        black_box(op);
+        offset = next;
+    }
+}
+
+fn fetch_pairs(program: &[(OpCode, usize)]) {
+    for &(op, _) in program {
+        black_box(op);
+    }
+}
+
+fn main() {
+    let mut heap = Heap::new();
+    let script = compile(&large_program(), &mut heap);
+    let chunk = script
+        .constants
+        .iter()
+        .find_map(|constant| match constant {
+            Value::Obj(Object::FunctionObject(function)) => Some(*function),
+            _ => None,
+        })
+        .map(|function| heap.function(function).chunk.clone())
+        .expect("large program defines a function");
+
+    let mut pairs = Vec::new();
+    let mut offset = 0;
+    while offset < chunk.len() {
+        let (op, next) = chunk.decode(offset);
+        pairs.push((op, chunk.line(offset)));
+        offset = next;
+    }
+
+    println!("instructions: {count}", count = pairs.len());
+    println!(
+        "bytes: {bytes} as a chunk, {pair_bytes} as (OpCode, line) pairs",
+        bytes = chunk.size(),
+        pair_bytes = pairs.len() * size_of::<(OpCode, usize)>()
+    );
+    let chunk_time = time(|| (0..FETCH_PASSES).for_each(|_| fetch_chunk(&chunk)));
+    let pairs_time = time(|| (0..FETCH_PASSES).for_each(|_| fetch_pairs(&pairs)));
+    println!("fetch: {chunk_time:?} chunk, {pairs_time:?} pairs");
+}
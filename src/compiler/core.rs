@@ -1,11 +1,15 @@
+const LOCALS_MAX: usize = 256;
+const UPVALUES_MAX: usize = 256;
+const ARGS_MAX: usize = 255;
+
 use crate::{
-    constants::Constants,
-    program::{OpCode, Program},
-    tokenizer::{OTokenResult, Token, TokenType, Tokenizer},
-    value::Value,
+    heap::{Heap, HeapObject},
+    program::{Chunk, OpCode, JUMP_MAX, OPERAND_MAX},
+    tokenizer::{unescape, OTokenResult, Token, TokenType, Tokenizer},
+    value::{Function, UpvalueRef, Value},
 };
 
-use super::types::CompilerErrors;
+use super::types::{ClassState, CompilerErrors, FunctionState, FunctionType, Local};
 use super::{
     precedence::{get_rule, Precedence},
     types::CompilerErr,
@@ -16,48 +20,364 @@ pub struct Compiler<'a> {
     tokenizer: Tokenizer<'a>,
     panic_mode: bool,
     pub had_error: bool,
+    /// REPL mode: top-level expression statements print their value and
+    /// the final one may omit its semicolon.
+    pub print_expressions: bool,
+    /// Set while compiling a declaration directly in the script body, and
+    /// cleared as soon as a statement starts, so nested statements such as
+    /// an unbraced loop body never print.
+    top_level_declaration: bool,
     current_token: Option<Token>,
     previous_token: Option<Token>,
-    pub constants: Constants,
-    pub strings: Vec<String>,
-    pub program: Program,
+    heap: &'a mut Heap,
     pub errors: CompilerErrors,
+    states: Vec<FunctionState>,
+    classes: Vec<ClassState>,
 }
 
 impl<'source> Compiler<'source> {
-    pub fn new(source: &'source [char], tokenizer: Tokenizer<'source>) -> Self {
+    pub fn new(
+        source: &'source [char],
+        tokenizer: Tokenizer<'source>,
+        heap: &'source mut Heap,
+    ) -> Self {
         Self {
             source,
             tokenizer,
             panic_mode: false,
             had_error: false,
+            print_expressions: false,
+            top_level_declaration: false,
             current_token: None,
             previous_token: None,
-            program: vec![],
             errors: vec![],
-            strings: Vec::new(),
-            constants: Constants::new(),
+            heap,
+            states: vec![FunctionState::new(FunctionType::Script, None)],
+            classes: Vec::new(),
         }
     }
 
-    pub fn compile(&mut self) {
+    pub fn compile(&mut self) -> Function {
         self.advance();
+        while self.current_token.is_some() {
+            self.top_level_declaration = true;
+            self.declaration();
+        }
+        self.end_function()
+    }
+
+    pub fn declaration(&mut self) {
+        if self.match_token(TokenType::Class) {
+            self.class_declaration();
+        } else if self.match_token(TokenType::Func) {
+            self.fun_declaration();
+        } else if self.match_token(TokenType::Var) {
+            self.var_declaration();
+        } else {
+            self.statement();
+        }
+
+        if self.panic_mode {
+            self.synchronize();
+        }
+    }
+
+    pub fn class_declaration(&mut self) {
+        use TokenType::{Identifier, LeftBrace, Less, RightBrace};
+
+        self.advance_match(Identifier, "Expect class name.");
+        let Some(class_name) = self.previous_token else {
+            return;
+        };
+        let line = class_name.line;
+        let name = self.token_name(class_name);
+        let name_constant = self.string_constant(name.clone());
+        self.declare_variable(class_name);
+
+        self.emit(OpCode::OpClass(name_constant), line);
+        self.define_variable(name_constant, line);
+
+        self.classes.push(ClassState::default());
+
+        if self.match_token(Less) {
+            self.advance_match(Identifier, "Expect superclass name.");
+            self.variable(false);
+            if self.previous_token.map(|t| self.token_name(t)) == Some(name.clone()) {
+                self.error("A class can't inherit from itself.");
+            }
+
+            self.begin_scope();
+            self.add_local("super".to_string());
+            self.define_variable(0, line);
+
+            self.named_variable(name.clone(), line, false);
+            self.emit(OpCode::OpInherit, line);
+            if let Some(class) = self.classes.last_mut() {
+                class.has_superclass = true;
+            }
+        }
+
+        self.named_variable(name, line, false);
+        self.advance_match(LeftBrace, "Expect '{' before class body.");
+        while self.current_token.is_some() && !self.check(RightBrace) {
+            self.method();
+        }
+        self.advance_match(RightBrace, "Expect '}' after class body.");
+        let line = self.previous_line();
+        self.emit(OpCode::OpPop, line);
+
+        if self.classes.pop().is_some_and(|class| class.has_superclass) {
+            self.end_scope();
+        }
+    }
+
+    pub fn method(&mut self) {
+        self.advance_match(TokenType::Identifier, "Expect method name.");
+        let Some(token) = self.previous_token else {
+            return;
+        };
+        let name = self.token_name(token);
+        let constant = self.string_constant(name.clone());
+
+        let function_type = if name == "init" {
+            FunctionType::Initializer
+        } else {
+            FunctionType::Method
+        };
+        self.function(function_type, Some(name));
+        self.emit(OpCode::OpMethod(constant), token.line);
+    }
+
+    pub fn fun_declaration(&mut self) {
+        let global = self.parse_variable("Expect function name.");
+        let line = self.previous_line();
+        self.mark_initialized();
+
+        let name = self.previous_token.map(|token| self.token_name(token));
+        self.function(FunctionType::Function, name);
+        self.define_variable(global, line);
+    }
+
+    pub fn function(&mut self, function_type: FunctionType, name: Option<String>) {
+        use TokenType::{Comma, LeftBrace, RightParen};
+
+        self.states.push(FunctionState::new(function_type, name));
+        self.begin_scope();
+
+        self.advance_match(TokenType::LeftParen, "Expect '(' after function name.");
+        if !self.check(RightParen) {
+            loop {
+                self.state_mut().function.arity += 1;
+                if self.state().function.arity > ARGS_MAX {
+                    self.error_at_current("Can't have more than 255 parameters.");
+                }
+                let constant = self.parse_variable("Expect parameter name.");
+                let line = self.previous_line();
+                self.define_variable(constant, line);
+
+                if !self.match_token(Comma) {
+                    break;
+                }
+            }
+        }
+        self.advance_match(RightParen, "Expect ')' after parameters.");
+        self.advance_match(LeftBrace, "Expect '{' before function body.");
+        self.block();
+
+        let line = self.previous_line();
+        let function = self.end_function();
+        let function = self.heap.alloc(HeapObject::Function(function));
+        let idx = self.make_constant(Value::new_function(function));
+        self.emit(OpCode::OpClosure(idx), line);
+    }
+
+    pub fn var_declaration(&mut self) {
+        let global = self.parse_variable("Expect variable name.");
+        let line = self.previous_line();
+
+        if self.match_token(TokenType::Equal) {
+            self.expression();
+        } else {
+            self.emit(OpCode::OpNil, line);
+        }
+        self.advance_match(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        );
+
+        self.define_variable(global, line);
+    }
+
+    pub fn statement(&mut self) {
+        use TokenType::{For, If, LeftBrace, Print, Return, While};
+
+        let top_level = std::mem::take(&mut self.top_level_declaration);
+        if self.match_token(Print) {
+            self.print_statement();
+        } else if self.match_token(Return) {
+            self.return_statement();
+        } else if self.match_token(If) {
+            self.if_statement();
+        } else if self.match_token(While) {
+            self.while_statement();
+        } else if self.match_token(For) {
+            self.for_statement();
+        } else if self.match_token(LeftBrace) {
+            self.begin_scope();
+            self.block();
+            self.end_scope();
+        } else {
+            self.expression_statement(top_level && self.states.len() == 1);
+        }
+    }
+
+    pub fn print_statement(&mut self) {
+        let line = self.previous_line();
         self.expression();
-        self.advance_end();
-        self.program.push((OpCode::OpReturn, 0));
+        self.advance_match(TokenType::Semicolon, "Expect ';' after value.");
+        self.emit(OpCode::OpPrint, line);
     }
 
-    pub fn grouping(&mut self) {
+    pub fn return_statement(&mut self) {
+        let line = self.previous_line();
+        if self.state().function_type == FunctionType::Script {
+            self.error("Can't return from top-level code.");
+        }
+
+        if self.match_token(TokenType::Semicolon) {
+            self.emit_return(line);
+        } else {
+            if self.state().function_type == FunctionType::Initializer {
+                self.error("Can't return a value from an initializer.");
+            }
+            self.expression();
+            self.advance_match(TokenType::Semicolon, "Expect ';' after return value.");
+            self.emit(OpCode::OpReturn, line);
+        }
+    }
+
+    pub fn if_statement(&mut self) {
+        use OpCode::{OpJump, OpJumpIfFalse, OpPop};
+
+        self.advance_match(TokenType::LeftParen, "Expect '(' after 'if'.");
+        self.expression();
+        self.advance_match(TokenType::RightParen, "Expect ')' after condition.");
+        let line = self.previous_line();
+
+        let then_jump = self.emit_jump(OpJumpIfFalse(0), line);
+        self.emit(OpPop, line);
+        self.statement();
+
+        let else_jump = self.emit_jump(OpJump(0), line);
+        self.patch_jump(then_jump);
+        self.emit(OpPop, line);
+
+        if self.match_token(TokenType::Else) {
+            self.statement();
+        }
+        self.patch_jump(else_jump);
+    }
+
+    pub fn while_statement(&mut self) {
+        use OpCode::{OpJumpIfFalse, OpPop};
+
+        let loop_start = self.current_chunk().len();
+        self.advance_match(TokenType::LeftParen, "Expect '(' after 'while'.");
+        self.expression();
+        self.advance_match(TokenType::RightParen, "Expect ')' after condition.");
+        let line = self.previous_line();
+
+        let exit_jump = self.emit_jump(OpJumpIfFalse(0), line);
+        self.emit(OpPop, line);
+        self.statement();
+        self.emit_loop(loop_start, line);
+
+        self.patch_jump(exit_jump);
+        self.emit(OpPop, line);
+    }
+
+    pub fn for_statement(&mut self) {
+        use OpCode::{OpJump, OpJumpIfFalse, OpPop};
+        use TokenType::{LeftParen, RightParen, Semicolon, Var};
+
+        self.begin_scope();
+        self.advance_match(LeftParen, "Expect '(' after 'for'.");
+        if self.match_token(Semicolon) {
+            // No initializer.
+        } else if self.match_token(Var) {
+            self.var_declaration();
+        } else {
+            self.expression_statement(false);
+        }
+
+        let mut loop_start = self.current_chunk().len();
+        let mut exit_jump = None;
+        if !self.match_token(Semicolon) {
+            self.expression();
+            self.advance_match(Semicolon, "Expect ';' after loop condition.");
+            let line = self.previous_line();
+
+            exit_jump = Some(self.emit_jump(OpJumpIfFalse(0), line));
+            self.emit(OpPop, line);
+        }
+
+        if !self.match_token(RightParen) {
+            let line = self.previous_line();
+            let body_jump = self.emit_jump(OpJump(0), line);
+            let increment_start = self.current_chunk().len();
+            self.expression();
+            self.emit(OpPop, line);
+            self.advance_match(RightParen, "Expect ')' after for clauses.");
+
+            self.emit_loop(loop_start, line);
+            loop_start = increment_start;
+            self.patch_jump(body_jump);
+        }
+
+        self.statement();
+        let line = self.previous_line();
+        self.emit_loop(loop_start, line);
+
+        if let Some(exit_jump) = exit_jump {
+            self.patch_jump(exit_jump);
+            self.emit(OpPop, line);
+        }
+        self.end_scope();
+    }
+
+    /// In REPL mode a `top_level` expression statement prints its value.
+    pub fn expression_statement(&mut self, top_level: bool) {
+        self.expression();
+        let line = self.previous_line();
+        if self.print_expressions && top_level {
+            if self.current_token.is_some() {
+                self.advance_match(TokenType::Semicolon, "Expect ';' after expression.");
+            }
+            self.emit(OpCode::OpPrint, line);
+        } else {
+            self.advance_match(TokenType::Semicolon, "Expect ';' after expression.");
+            self.emit(OpCode::OpPop, line);
+        }
+    }
+
+    pub fn block(&mut self) {
+        while self.current_token.is_some() && !self.check(TokenType::RightBrace) {
+            self.declaration();
+        }
+        self.advance_match(TokenType::RightBrace, "Expect '}' after block.");
+    }
+
+    pub fn grouping(&mut self, can_assign: bool) {
         self.expression();
         self.advance_match(TokenType::RightParen, "Expect ')' after expression");
     }
 
-    pub fn unary(&mut self) {
-        use OpCode::{OpNegate, OpNot};
+    pub fn unary(&mut self, can_assign: bool) {
+        use OpCode::{OpBitNot, OpNegate, OpNot};
         use Precedence::Unary;
-        use TokenType::{Bang, Minus};
+        use TokenType::{Bang, Minus, Tilde};
 
-        let previous_token = self.previous_token.clone();
+        let previous_token = self.previous_token;
 
         self.parse_precedence(Unary);
 
@@ -66,105 +386,323 @@ impl<'source> Compiler<'source> {
                 token_type: Minus,
                 line,
                 ..
-            }) => self.program.push((OpNegate, line)),
+            }) => self.emit(OpNegate, line),
             Some(Token {
                 token_type: Bang,
                 line,
                 ..
-            }) => self.program.push((OpNot, line)),
+            }) => self.emit(OpNot, line),
+            Some(Token {
+                token_type: Tilde,
+                line,
+                ..
+            }) => self.emit(OpBitNot, line),
             _ => {}
         }
     }
 
-    pub fn binary(&mut self) {
+    pub fn binary(&mut self, can_assign: bool) {
         use OpCode::{
-            OpAdd, OpDivide, OpEqual, OpGreater, OpGreaterEqual, OpLess, OpLessEqual, OpMultiply,
-            OpNotEqual, OpSubtract,
+            OpAdd, OpBitAnd, OpBitOr, OpBitXor, OpDivide, OpEqual, OpGreater, OpGreaterEqual,
+            OpIntDivide, OpLess, OpLessEqual, OpModulo, OpMultiply, OpNotEqual, OpPower,
+            OpShiftLeft, OpShiftRight, OpSubtract,
         };
         use TokenType::{
-            BangEqual, EqualEqual, Greater, GreaterEqual, Less, LessEqual, Minus, Plus, Slash, Star,
+            Ampersand, BangEqual, Caret, EqualEqual, Greater, GreaterEqual, GreaterGreater, Less,
+            LessEqual, LessLess, Minus, Percent, Pipe, Plus, Slash, Star, StarStar, TildeSlash,
         };
 
-        if let Some(token) = self.previous_token.clone() {
+        if let Some(token) = self.previous_token {
             let rule = get_rule(token.token_type);
 
-            self.parse_precedence(rule.precedence + 1);
+            // `**` is right-associative.
+            if token.token_type == StarStar {
+                self.parse_precedence(rule.precedence);
+            } else {
+                self.parse_precedence(rule.precedence + 1);
+            }
 
             match token.token_type {
-                Plus => self.program.push((OpAdd, token.line)),
-                Minus => self.program.push((OpSubtract, token.line)),
-                Star => self.program.push((OpMultiply, token.line)),
-                Slash => self.program.push((OpDivide, token.line)),
-                BangEqual => self.program.push((OpNotEqual, token.line)),
-                EqualEqual => self.program.push((OpEqual, token.line)),
-                Greater => self.program.push((OpGreater, token.line)),
-                GreaterEqual => self.program.push((OpGreaterEqual, token.line)),
-                Less => self.program.push((OpLess, token.line)),
-                LessEqual => self.program.push((OpLessEqual, token.line)),
+                Plus => self.emit(OpAdd, token.line),
+                Minus => self.emit(OpSubtract, token.line),
+                Star => self.emit(OpMultiply, token.line),
+                Slash => self.emit(OpDivide, token.line),
+                TildeSlash => self.emit(OpIntDivide, token.line),
+                Percent => self.emit(OpModulo, token.line),
+                StarStar => self.emit(OpPower, token.line),
+                Ampersand => self.emit(OpBitAnd, token.line),
+                Pipe => self.emit(OpBitOr, token.line),
+                Caret => self.emit(OpBitXor, token.line),
+                LessLess => self.emit(OpShiftLeft, token.line),
+                GreaterGreater => self.emit(OpShiftRight, token.line),
+                BangEqual => self.emit(OpNotEqual, token.line),
+                EqualEqual => self.emit(OpEqual, token.line),
+                Greater => self.emit(OpGreater, token.line),
+                GreaterEqual => self.emit(OpGreaterEqual, token.line),
+                Less => self.emit(OpLess, token.line),
+                LessEqual => self.emit(OpLessEqual, token.line),
                 _ => {}
             }
         }
     }
 
-    pub fn number(&mut self) {
-        use OpCode::OpConstant;
+    pub fn call(&mut self, can_assign: bool) {
+        let line = self.previous_line();
+        let argc = self.argument_list();
+        self.emit(OpCode::OpCall(argc), line);
+    }
 
-        match self.previous_token {
-            Some(Token {
-                pos, length, line, ..
-            }) => {
-                let num = self.source[pos..(pos + length)]
-                    .iter()
-                    .collect::<String>()
-                    .parse::<f64>();
-                let num = match num {
-                    Ok(num) => num,
-                    Err(_) => {
-                        self.error("Failed to parse number, defaulting to 0");
-                        0 as f64
-                    }
-                };
-
-                let idx = self.constants.push(Value::Float(num));
-                self.program.push((OpConstant(idx), line))
+    pub fn list(&mut self, can_assign: bool) {
+        let line = self.previous_line();
+        let mut count = 0;
+        while !self.check(TokenType::RightBracket) {
+            self.expression();
+            count += 1;
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
+        self.advance_match(TokenType::RightBracket, "Expect ']' after list elements.");
+        self.emit(OpCode::OpBuildList(count), line);
+    }
+
+    /// A `{` in expression position starts a map literal; blocks only appear
+    /// where a statement is expected.
+    pub fn map(&mut self, can_assign: bool) {
+        let line = self.previous_line();
+        let mut count = 0;
+        while !self.check(TokenType::RightBrace) {
+            self.expression();
+            self.advance_match(TokenType::Colon, "Expect ':' after map key.");
+            self.expression();
+            count += 1;
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
+        self.advance_match(TokenType::RightBrace, "Expect '}' after map entries.");
+        self.emit(OpCode::OpBuildMap(count), line);
+    }
+
+    pub fn subscript(&mut self, can_assign: bool) {
+        use OpCode::{OpGetIndex, OpSetIndex};
+
+        let line = self.previous_line();
+        self.expression();
+        self.advance_match(TokenType::RightBracket, "Expect ']' after index.");
+
+        if can_assign && self.match_token(TokenType::Equal) {
+            self.expression();
+            self.emit(OpSetIndex, line);
+        } else {
+            self.emit(OpGetIndex, line);
+        }
+    }
+
+    pub fn dot(&mut self, can_assign: bool) {
+        use OpCode::{OpGetProperty, OpInvoke, OpSetProperty};
+
+        self.advance_match(TokenType::Identifier, "Expect property name after '.'.");
+        let Some(token) = self.previous_token else {
+            return;
+        };
+        let name = self.identifier_constant(token);
+
+        if can_assign && self.match_token(TokenType::Equal) {
+            self.expression();
+            self.emit(OpSetProperty(name), token.line);
+        } else if self.match_token(TokenType::LeftParen) {
+            let argc = self.argument_list();
+            self.emit(OpInvoke(name, argc), token.line);
+        } else {
+            self.emit(OpGetProperty(name), token.line);
+        }
+    }
+
+    pub fn this(&mut self, can_assign: bool) {
+        if self.classes.is_empty() {
+            self.error("Can't use 'this' outside of a class.");
+            return;
+        }
+        let line = self.previous_line();
+        self.named_variable("this".to_string(), line, false);
+    }
+
+    pub fn super_(&mut self, can_assign: bool) {
+        use OpCode::{OpGetSuper, OpSuperInvoke};
+
+        match self.classes.last() {
+            None => self.error("Can't use 'super' outside of a class."),
+            Some(class) if !class.has_superclass => {
+                self.error("Can't use 'super' in a class with no superclass.")
             }
-            None => {}
+            Some(_) => {}
+        }
+
+        self.advance_match(TokenType::Dot, "Expect '.' after 'super'.");
+        self.advance_match(TokenType::Identifier, "Expect superclass method name.");
+        let Some(token) = self.previous_token else {
+            return;
+        };
+        let name = self.identifier_constant(token);
+
+        self.named_variable("this".to_string(), token.line, false);
+        if self.match_token(TokenType::LeftParen) {
+            let argc = self.argument_list();
+            self.named_variable("super".to_string(), token.line, false);
+            self.emit(OpSuperInvoke(name, argc), token.line);
+        } else {
+            self.named_variable("super".to_string(), token.line, false);
+            self.emit(OpGetSuper(name), token.line);
+        }
+    }
+
+    pub fn and(&mut self, can_assign: bool) {
+        use OpCode::{OpJumpIfFalse, OpPop};
+
+        let line = self.previous_line();
+        let end_jump = self.emit_jump(OpJumpIfFalse(0), line);
+
+        self.emit(OpPop, line);
+        self.parse_precedence(Precedence::And);
+
+        self.patch_jump(end_jump);
+    }
+
+    pub fn or(&mut self, can_assign: bool) {
+        use OpCode::{OpJump, OpJumpIfFalse, OpPop};
+
+        let line = self.previous_line();
+        let else_jump = self.emit_jump(OpJumpIfFalse(0), line);
+        let end_jump = self.emit_jump(OpJump(0), line);
+
+        self.patch_jump(else_jump);
+        self.emit(OpPop, line);
+
+        self.parse_precedence(Precedence::Or);
+        self.patch_jump(end_jump);
+    }
+
+    pub fn number(&mut self, can_assign: bool) {
+        use OpCode::OpConstant;
+
+        if let Some(Token {
+            pos, length, line, ..
+        }) = self.previous_token
+        {
+            let literal = self.source[pos..(pos + length)]
+                .iter()
+                .filter(|&&c| c != '_')
+                .collect::<String>();
+            let radix = match literal.get(..2) {
+                Some("0x" | "0X") => 16,
+                Some("0b" | "0B") => 2,
+                Some("0o" | "0O") => 8,
+                _ => 10,
+            };
+            let num = if radix != 10 {
+                i64::from_str_radix(&literal[2..], radix)
+                    .map(Value::Int)
+                    .ok()
+            } else if literal.contains(['.', 'e', 'E']) {
+                literal.parse::<f64>().map(Value::Float).ok()
+            } else {
+                literal.parse::<i64>().map(Value::Int).ok()
+            };
+            let Some(num) = num else {
+                return self.error("Integer literal is too large.");
+            };
+
+            let idx = self.make_constant(num);
+            self.emit(OpConstant(idx), line)
         }
     }
 
-    pub fn literal(&mut self) {
+    pub fn literal(&mut self, can_assign: bool) {
         use OpCode::{OpFalse, OpNil, OpTrue};
         use TokenType::{FalseIdent, Nil, TrueIdent};
 
-        if let Some(token) = self.previous_token.clone() {
+        if let Some(token) = self.previous_token {
             match token.token_type {
-                FalseIdent => self.program.push((OpFalse, token.line)),
-                TrueIdent => self.program.push((OpTrue, token.line)),
-                Nil => self.program.push((OpNil, token.line)),
+                FalseIdent => self.emit(OpFalse, token.line),
+                TrueIdent => self.emit(OpTrue, token.line),
+                Nil => self.emit(OpNil, token.line),
                 _ => {}
             }
         }
     }
 
-    pub fn string(&mut self) {
-        use OpCode::OpConstant;
+    pub fn string(&mut self, can_assign: bool) {
+        self.string_segment(1);
+    }
 
-        match self.previous_token {
-            Some(Token {
-                pos, length, line, ..
-            }) => {
-                let string = self.source[(pos + 1)..(pos + length - 2)]
-                    .iter()
-                    .collect::<String>();
-                self.strings.push(string);
-
-                let idx = self
-                    .constants
-                    .push(Value::new_string(self.strings.len() - 2));
-
-                self.program.push((OpConstant(idx), line))
+    /// `"a ${x} b"` compiles to `"a " + str(x) + " b"`; the tokenizer hands
+    /// over one `Interpolation` token per `${` and a closing `StringLiteral`.
+    pub fn interpolation(&mut self, can_assign: bool) {
+        use OpCode::{OpAdd, OpStringify};
+
+        self.string_segment(2);
+        loop {
+            let line = self.previous_line();
+            self.expression();
+            self.emit(OpStringify, line);
+            self.emit(OpAdd, line);
+
+            if self.match_token(TokenType::Interpolation) {
+                self.string_segment(2);
+            } else if self.match_token(TokenType::StringLiteral) {
+                self.string_segment(1);
+            } else {
+                self.error_at_current("Expect '}' after interpolated expression.");
+                return;
             }
-            None => {}
+            self.emit(OpAdd, self.previous_line());
+
+            if self.previous_token.map(|t| t.token_type) != Some(TokenType::Interpolation) {
+                break;
+            }
+        }
+    }
+
+    /// Emits the previous string token's text without its opening delimiter
+    /// and the last `closing` characters.
+    fn string_segment(&mut self, closing: usize) {
+        use OpCode::OpConstant;
+
+        if let Some(Token {
+            pos, length, line, ..
+        }) = self.previous_token
+        {
+            let string = match unescape(&self.source[(pos + 1)..(pos + length - closing)]) {
+                Ok(string) => string,
+                Err(msg) => return self.error(&msg),
+            };
+            let idx = self.string_constant(string);
+
+            self.emit(OpConstant(idx), line)
+        }
+    }
+
+    pub fn raw_string(&mut self, can_assign: bool) {
+        use OpCode::OpConstant;
+
+        if let Some(Token {
+            pos, length, line, ..
+        }) = self.previous_token
+        {
+            let string = self.source[(pos + 2)..(pos + length - 1)]
+                .iter()
+                .collect::<String>();
+            let idx = self.string_constant(string);
+
+            self.emit(OpConstant(idx), line)
+        }
+    }
+
+    pub fn variable(&mut self, can_assign: bool) {
+        if let Some(token) = self.previous_token {
+            let name = self.token_name(token);
+            self.named_variable(name, token.line, can_assign);
         }
     }
 }
@@ -174,8 +712,268 @@ impl<'source> Compiler<'source> {
         self.parse_precedence(Precedence::Assignment)
     }
 
+    fn state(&self) -> &FunctionState {
+        self.states
+            .last()
+            .expect("compiler always has a function state")
+    }
+
+    fn state_mut(&mut self) -> &mut FunctionState {
+        self.states
+            .last_mut()
+            .expect("compiler always has a function state")
+    }
+
+    fn current_chunk(&mut self) -> &mut Chunk {
+        &mut self.state_mut().function.chunk
+    }
+
+    fn emit(&mut self, op: OpCode, line: usize) {
+        self.current_chunk().write(op, line);
+    }
+
+    fn emit_return(&mut self, line: usize) {
+        if self.state().function_type == FunctionType::Initializer {
+            self.emit(OpCode::OpGetLocal(0), line);
+        } else {
+            self.emit(OpCode::OpNil, line);
+        }
+        self.emit(OpCode::OpReturn, line);
+    }
+
+    fn end_function(&mut self) -> Function {
+        let line = self.previous_line();
+        self.emit_return(line);
+        self.states
+            .pop()
+            .expect("compiler always has a function state")
+            .function
+    }
+
+    fn make_constant(&mut self, value: Value) -> usize {
+        let idx = self.state_mut().function.constants.push(value);
+        if idx > OPERAND_MAX {
+            self.error("Too many constants in one chunk.");
+            return 0;
+        }
+        idx
+    }
+
+    fn string_constant(&mut self, string: String) -> usize {
+        let string = self.heap.intern(string);
+        self.make_constant(Value::new_string(string))
+    }
+
+    fn argument_list(&mut self) -> usize {
+        let mut argc = 0;
+        if !self.check(TokenType::RightParen) {
+            loop {
+                self.expression();
+                if argc == ARGS_MAX {
+                    self.error("Can't have more than 255 arguments.");
+                }
+                argc += 1;
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.advance_match(TokenType::RightParen, "Expect ')' after arguments.");
+        argc
+    }
+
+    fn identifier_constant(&mut self, token: Token) -> usize {
+        let name = self.token_name(token);
+        self.string_constant(name)
+    }
+
+    fn token_name(&self, token: Token) -> String {
+        self.source[token.pos..(token.pos + token.length)]
+            .iter()
+            .collect::<String>()
+    }
+
+    fn parse_variable(&mut self, error_msg: &str) -> usize {
+        self.advance_match(TokenType::Identifier, error_msg);
+        match self.previous_token {
+            Some(token) if token.token_type == TokenType::Identifier => {
+                self.declare_variable(token);
+                if self.state().scope_depth > 0 {
+                    return 0;
+                }
+                self.identifier_constant(token)
+            }
+            _ => 0,
+        }
+    }
+
+    fn define_variable(&mut self, global: usize, line: usize) {
+        if self.state().scope_depth > 0 {
+            self.mark_initialized();
+            return;
+        }
+        self.emit(OpCode::OpDefineGlobal(global), line);
+    }
+
+    fn declare_variable(&mut self, name: Token) {
+        if self.state().scope_depth == 0 {
+            return;
+        }
+
+        let name = self.token_name(name);
+        let state = self.state();
+        let redeclared = state
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth.is_none_or(|d| d >= state.scope_depth))
+            .any(|local| local.name == name);
+        if redeclared {
+            self.error("Already a variable with this name in this scope.");
+        }
+
+        self.add_local(name);
+    }
+
+    fn add_local(&mut self, name: String) {
+        if self.state().locals.len() == LOCALS_MAX {
+            self.error("Too many local variables in function.");
+            return;
+        }
+        self.state_mut().locals.push(Local::new(name));
+    }
+
+    fn mark_initialized(&mut self) {
+        let state = self.state_mut();
+        if state.scope_depth == 0 {
+            return;
+        }
+        if let Some(local) = state.locals.last_mut() {
+            local.depth = Some(state.scope_depth);
+        }
+    }
+
+    fn resolve_local(&mut self, depth: usize, name: &str) -> Option<usize> {
+        let (slot, local) = self.states[depth]
+            .locals
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, local)| local.name == name)?;
+
+        if local.depth.is_none() {
+            self.error("Can't read local variable in its own initializer.");
+        }
+        Some(slot)
+    }
+
+    fn resolve_upvalue(&mut self, depth: usize, name: &str) -> Option<usize> {
+        let enclosing = depth.checked_sub(1)?;
+
+        if let Some(slot) = self.resolve_local(enclosing, name) {
+            self.states[enclosing].locals[slot].is_captured = true;
+            return Some(self.add_upvalue(depth, slot, true));
+        }
+
+        let index = self.resolve_upvalue(enclosing, name)?;
+        Some(self.add_upvalue(depth, index, false))
+    }
+
+    fn add_upvalue(&mut self, depth: usize, index: usize, is_local: bool) -> usize {
+        let upvalue = UpvalueRef { index, is_local };
+        let upvalues = &self.states[depth].function.upvalues;
+        if let Some(existing) = upvalues.iter().position(|u| *u == upvalue) {
+            return existing;
+        }
+        if upvalues.len() == UPVALUES_MAX {
+            self.error("Too many closure variables in function.");
+            return 0;
+        }
+
+        let upvalues = &mut self.states[depth].function.upvalues;
+        upvalues.push(upvalue);
+        upvalues.len() - 1
+    }
+
+    fn named_variable(&mut self, name: String, line: usize, can_assign: bool) {
+        use OpCode::{
+            OpGetGlobal, OpGetLocal, OpGetUpvalue, OpSetGlobal, OpSetLocal, OpSetUpvalue,
+        };
+
+        let depth = self.states.len() - 1;
+        let (get_op, set_op) = if let Some(slot) = self.resolve_local(depth, &name) {
+            (OpGetLocal(slot), OpSetLocal(slot))
+        } else if let Some(index) = self.resolve_upvalue(depth, &name) {
+            (OpGetUpvalue(index), OpSetUpvalue(index))
+        } else {
+            let arg = self.string_constant(name);
+            (OpGetGlobal(arg), OpSetGlobal(arg))
+        };
+
+        if can_assign && self.match_token(TokenType::Equal) {
+            self.expression();
+            self.emit(set_op, line);
+        } else {
+            self.emit(get_op, line);
+        }
+    }
+
+    /// Emits a jump with a placeholder distance and returns its offset for
+    /// `patch_jump`.
+    fn emit_jump(&mut self, op: OpCode, line: usize) -> usize {
+        let offset = self.current_chunk().len();
+        self.emit(op, line);
+        offset
+    }
+
+    fn patch_jump(&mut self, offset: usize) {
+        let chunk = self.current_chunk();
+        let (_, next) = chunk.decode(offset);
+        let distance = chunk.len() - next;
+        if distance > JUMP_MAX {
+            self.error("Too much code to jump over.");
+            return;
+        }
+        self.current_chunk().patch_jump(offset, distance);
+    }
+
+    fn emit_loop(&mut self, loop_start: usize, line: usize) {
+        // The distance is taken from the end of the three byte loop instruction.
+        let distance = self.current_chunk().len() + 3 - loop_start;
+        if distance > JUMP_MAX {
+            self.error("Loop body too large.");
+        }
+        self.emit(OpCode::OpLoop(distance.min(JUMP_MAX)), line);
+    }
+
+    fn begin_scope(&mut self) {
+        self.state_mut().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.state_mut().scope_depth -= 1;
+
+        let line = self.previous_line();
+        loop {
+            let state = self.state_mut();
+            let scope_depth = state.scope_depth;
+            match state.locals.last() {
+                Some(local) if local.depth.is_none_or(|d| d > scope_depth) => {
+                    let op = if local.is_captured {
+                        OpCode::OpCloseUpvalue
+                    } else {
+                        OpCode::OpPop
+                    };
+                    state.locals.pop();
+                    self.emit(op, line);
+                }
+                _ => break,
+            }
+        }
+    }
+
     fn error_at_current(&mut self, msg: &str) {
-        let current_token: OTokenResult = self.current_token.clone().map(|t| Ok(t));
+        let current_token: OTokenResult = self.current_token.map(Ok);
         self.error_at(&current_token, msg)
     }
 
@@ -184,34 +982,56 @@ impl<'source> Compiler<'source> {
             return;
         }
         self.panic_mode = true;
-        self.errors.push(CompilerErr::new(token, msg));
+        let end_line = self.tokenizer.line();
+        self.errors.push(CompilerErr::new(token, end_line, msg));
         self.had_error = true;
     }
 
     fn error(&mut self, msg: &str) {
-        let previous_token: OTokenResult = self.previous_token.clone().map(|t| Ok(t));
+        let previous_token: OTokenResult = self.previous_token.map(Ok);
         self.error_at(&previous_token, msg)
     }
 
     fn advance_match(&mut self, expected_token_type: TokenType, error_msg: &str) {
-        match self.current_token {
-            Some(t) => {
-                if t.token_type == expected_token_type {
-                    self.advance();
-                    return;
-                };
-            }
-            None => {}
+        if self.check(expected_token_type) {
+            self.advance();
+            return;
         }
 
         self.error_at_current(error_msg);
     }
 
-    fn advance_end(&mut self) {
+    fn check(&self, token_type: TokenType) -> bool {
+        matches!(self.current_token, Some(t) if t.token_type == token_type)
+    }
+
+    fn match_token(&mut self, token_type: TokenType) -> bool {
+        if !self.check(token_type) {
+            return false;
+        }
         self.advance();
-        match self.current_token {
-            None => {}
-            Some(_) => self.error_at_current("Expected end of token stream"),
+        true
+    }
+
+    fn previous_line(&self) -> usize {
+        self.previous_token
+            .map(|t| t.line)
+            .unwrap_or_else(|| self.tokenizer.get_current_line())
+    }
+
+    fn synchronize(&mut self) {
+        use TokenType::*;
+
+        self.panic_mode = false;
+
+        while let Some(token) = self.current_token {
+            if matches!(self.previous_token, Some(t) if t.token_type == Semicolon) {
+                return;
+            }
+            match token.token_type {
+                Class | Func | Var | For | If | While | Print | Return => return,
+                _ => self.advance(),
+            }
         }
     }
 
@@ -242,9 +1062,10 @@ impl<'source> Compiler<'source> {
 
     fn parse_precedence(&mut self, precedence: Precedence) {
         self.advance();
+        let can_assign = precedence <= Precedence::Assignment;
         if let Some(token) = self.previous_token {
             match get_rule(token.token_type).prefix {
-                Some(op) => op(self),
+                Some(op) => op(self, can_assign),
                 None => self.error("Expect expression."),
             }
         }
@@ -253,15 +1074,19 @@ impl<'source> Compiler<'source> {
             let current_token = self.current_token.unwrap();
             let current_token_precedence = get_rule(current_token.token_type).precedence;
             if precedence > current_token_precedence {
-                return;
+                break;
             }
 
             self.advance();
             if let Some(previous_token) = self.previous_token {
                 if let Some(infix) = get_rule(previous_token.token_type).infix {
-                    infix(self);
+                    infix(self, can_assign);
                 }
             }
         }
+
+        if can_assign && self.match_token(TokenType::Equal) {
+            self.error("Invalid assignment target.");
+        }
     }
 }
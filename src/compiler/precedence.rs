@@ -12,10 +12,15 @@ pub enum Precedence {
     And,        // and
     Equality,   // == !=
     Comparison, // < > <= >=
+    BitOr,      // |
+    BitXor,     // ^
+    BitAnd,     // &
+    Shift,      // << >>
     Term,       // + -
-    Factor,     // * /
-    Unary,      // ! -
-    Call,       // . ()
+    Factor,     // * / ~/ %
+    Unary,      // ! - ~
+    Exponent,   // **
+    Call,       // . () []
     Primary,
 }
 
@@ -37,16 +42,21 @@ impl From<u8> for Precedence {
             3 => And,
             4 => Equality,
             5 => Comparison,
-            6 => Term,
-            7 => Factor,
-            8 => Unary,
-            9 => Call,
+            6 => BitOr,
+            7 => BitXor,
+            8 => BitAnd,
+            9 => Shift,
+            10 => Term,
+            11 => Factor,
+            12 => Unary,
+            13 => Exponent,
+            14 => Call,
             _ => Primary,
         }
     }
 }
 
-type Operation<'source> = fn(&mut Compiler<'source>);
+type Operation<'source> = fn(&mut Compiler<'source>, bool);
 
 type OOperation<'source> = Option<Operation<'source>>;
 
@@ -74,13 +84,29 @@ pub fn get_rule<'source>(token_type: TokenType) -> Rule<'source> {
     use Precedence::*;
     use TokenType::*;
     match token_type {
-        LeftParen => Rule::new(Some(Compiler::grouping), None, PrecNone),
+        LeftParen => Rule::new(Some(Compiler::grouping), Some(Compiler::call), Call),
+
+        LeftBracket => Rule::new(Some(Compiler::list), Some(Compiler::subscript), Call),
+
+        LeftBrace => Rule::new(Some(Compiler::map), None, PrecNone),
 
         Minus => Rule::new(Some(Compiler::unary), Some(Compiler::binary), Term),
 
         Plus => Rule::new(None, Some(Compiler::binary), Term),
 
-        Slash | Star => Rule::new(None, Some(Compiler::binary), Factor),
+        Slash | Star | TildeSlash | Percent => Rule::new(None, Some(Compiler::binary), Factor),
+
+        StarStar => Rule::new(None, Some(Compiler::binary), Exponent),
+
+        Pipe => Rule::new(None, Some(Compiler::binary), Precedence::BitOr),
+
+        Caret => Rule::new(None, Some(Compiler::binary), Precedence::BitXor),
+
+        Ampersand => Rule::new(None, Some(Compiler::binary), Precedence::BitAnd),
+
+        LessLess | GreaterGreater => Rule::new(None, Some(Compiler::binary), Shift),
+
+        Tilde => Rule::new(Some(Compiler::unary), None, PrecNone),
 
         NumericLiteral => Rule::new(Some(Compiler::number), None, PrecNone),
 
@@ -91,6 +117,20 @@ pub fn get_rule<'source>(token_type: TokenType) -> Rule<'source> {
         BangEqual | EqualEqual => Rule::new(None, Some(Compiler::binary), Equality),
 
         StringLiteral => Rule::new(Some(Compiler::string), None, PrecNone),
+        RawStringLiteral => Rule::new(Some(Compiler::raw_string), None, PrecNone),
+        Interpolation => Rule::new(Some(Compiler::interpolation), None, PrecNone),
+
+        Identifier => Rule::new(Some(Compiler::variable), None, PrecNone),
+
+        Dot => Rule::new(None, Some(Compiler::dot), Call),
+
+        This => Rule::new(Some(Compiler::this), None, PrecNone),
+
+        Super => Rule::new(Some(Compiler::super_), None, PrecNone),
+
+        TokenType::And => Rule::new(None, Some(Compiler::and), Precedence::And),
+
+        TokenType::Or => Rule::new(None, Some(Compiler::or), Precedence::Or),
 
         Greater | GreaterEqual | LessEqual | Less => {
             Rule::new(None, Some(Compiler::binary), Comparison)
@@ -1,17 +1,20 @@
 use std::fmt::Display;
 
-use crate::tokenizer::OTokenResult;
+use crate::{tokenizer::OTokenResult, value::Function};
 
 #[derive(Debug, Clone)]
 pub struct CompilerErr {
     token: OTokenResult,
+    /// The line input ended on, for errors reported at the end.
+    end_line: usize,
     content: String,
 }
 
 impl CompilerErr {
-    pub fn new(token: &OTokenResult, content: &str) -> Self {
+    pub fn new(token: &OTokenResult, end_line: usize, content: &str) -> Self {
         Self {
             token: token.to_owned(),
+            end_line,
             content: content.to_owned(),
         }
     }
@@ -20,19 +23,73 @@ impl CompilerErr {
 impl Display for CompilerErr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let (suffix, line) = match self.token.clone() {
-            None => (String::from(" at end"), 0),
+            None => (String::from(" at end"), self.end_line),
             Some(Ok(t)) => (
                 format!(" at ({start}, {length})", start = t.pos, length = t.length),
                 t.line,
             ),
-            Some(Err(t)) => (format!(""), t.line),
+            Some(Err(t)) => (String::new(), t.line),
         };
 
-        write!(
-            f,
-            "[line {line}]: Error {suffix}: {msg}",
-            msg = self.content
-        )
+        write!(f, "[line {line}]: Error{suffix}: {msg}", msg = self.content)
     }
 }
 pub type CompilerErrors = Vec<CompilerErr>;
+
+#[derive(Debug, Clone)]
+pub struct Local {
+    pub name: String,
+    pub depth: Option<usize>,
+    pub is_captured: bool,
+}
+
+impl Local {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            depth: None,
+            is_captured: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionType {
+    Function,
+    Initializer,
+    Method,
+    Script,
+}
+
+pub struct FunctionState {
+    pub function: Function,
+    pub function_type: FunctionType,
+    pub locals: Vec<Local>,
+    pub scope_depth: usize,
+}
+
+impl FunctionState {
+    pub fn new(function_type: FunctionType, name: Option<String>) -> Self {
+        // Slot zero holds the function being called, or the receiver for methods.
+        let callee = match function_type {
+            FunctionType::Method | FunctionType::Initializer => "this",
+            FunctionType::Function | FunctionType::Script => "",
+        };
+        let callee = Local {
+            name: callee.to_string(),
+            depth: Some(0),
+            is_captured: false,
+        };
+        Self {
+            function: Function::new(name),
+            function_type,
+            locals: vec![callee],
+            scope_depth: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClassState {
+    pub has_superclass: bool,
+}
@@ -10,6 +10,18 @@ impl Constants {
         self.0.push(val);
         self.0.len() - 1
     }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Value> {
+        self.0.iter()
+    }
+
     pub fn new() -> Self {
         Self {
             ..Default::default()
@@ -1,11 +1,83 @@
-use crate::constants::Constants;
+use crate::heap::Heap;
 use crate::program::OpCode;
+use crate::value::{Function, Object, Value};
 
-pub fn disassemble_instruction(
-    op: &(OpCode, usize),
-    constants: &Constants,
-    ip: usize,
-    prefix: &str,
-) {
-    println!("{prefix}{ip} {op:?} {line}", op = op.0, line = op.1);
+/// Disassembles `function` followed by every function nested in its
+/// constants, each under its own header.
+pub fn disassemble_function(heap: &Heap, function: &Function) -> String {
+    let mut out = disassemble_chunk(heap, function);
+    for constant in function.constants.iter() {
+        if let Value::Obj(Object::FunctionObject(nested)) = constant {
+            out.push('\n');
+            out.push_str(&disassemble_function(heap, heap.function(*nested)));
+        }
+    }
+    out
+}
+
+/// Disassembles the instructions of `function` alone, one per line.
+pub fn disassemble_chunk(heap: &Heap, function: &Function) -> String {
+    let name = function.name.as_deref().unwrap_or("<script>");
+    let mut out = format!("== {name} ==\n");
+    let mut offset = 0;
+    while offset < function.chunk.len() {
+        out.push_str(&disassemble_instruction(heap, function, offset));
+        out.push('\n');
+        offset = function.chunk.decode(offset).1;
+    }
+    out
+}
+
+/// Renders the instruction at `offset` with its line and operands. Lines
+/// shared with the previous instruction are shown as `|`.
+pub fn disassemble_instruction(heap: &Heap, function: &Function, offset: usize) -> String {
+    use OpCode::*;
+
+    let (op, next) = function.chunk.decode(offset);
+    let line = function.chunk.line(offset);
+    let line = match offset.checked_sub(1).map(|prev| function.chunk.line(prev)) {
+        Some(prev) if prev == line => "   |".to_string(),
+        _ => format!("{line:4}"),
+    };
+    let name = format!("{op:?}");
+    let name = name.split('(').next().unwrap_or_default();
+    let prefix = format!("{offset:04} {line} {name:<16}");
+
+    let constant = |idx: usize| {
+        format!(
+            "{prefix} {idx:4} '{value}'",
+            value = heap.format_value(&function.constants[idx])
+        )
+    };
+    let jump = |target: usize| format!("{prefix} {offset:4} -> {target}");
+
+    match op {
+        OpConstant(idx) | OpDefineGlobal(idx) | OpGetGlobal(idx) | OpSetGlobal(idx)
+        | OpClass(idx) | OpMethod(idx) | OpGetProperty(idx) | OpSetProperty(idx)
+        | OpGetSuper(idx) => constant(idx),
+        OpGetLocal(slot) | OpSetLocal(slot) | OpGetUpvalue(slot) | OpSetUpvalue(slot) => {
+            format!("{prefix} {slot:4}")
+        }
+        OpCall(count) | OpBuildList(count) | OpBuildMap(count) => format!("{prefix} {count:4}"),
+        OpJump(distance) | OpJumpIfFalse(distance) => jump(next + distance),
+        OpLoop(distance) => jump(next - distance),
+        OpInvoke(idx, argc) | OpSuperInvoke(idx, argc) => {
+            format!("{} ({argc} args)", constant(idx))
+        }
+        OpClosure(idx) => {
+            let mut out = constant(idx);
+            if let Value::Obj(Object::FunctionObject(closure)) = function.constants[idx] {
+                for upvalue in heap.function(closure).upvalues.iter() {
+                    let kind = if upvalue.is_local { "local" } else { "upvalue" };
+                    out.push_str(&format!(
+                        "\n{offset:04}    | {:<16} {kind} {index}",
+                        "",
+                        index = upvalue.index
+                    ));
+                }
+            }
+            out
+        }
+        _ => prefix.trim_end().to_string(),
+    }
 }
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+
+use crate::value::*;
+
+pub(super) const GC_MIN_THRESHOLD: usize = 1024 * 1024;
+pub(super) const GC_HEAP_GROW_FACTOR: usize = 2;
+
+#[derive(Debug, Clone)]
+pub enum HeapObject {
+    String(String),
+    Function(Function),
+    Closure(Closure),
+    Upvalue(Upvalue),
+    Class(Class),
+    Instance(Instance),
+    BoundMethod(BoundMethod),
+    Native(Native),
+    List(List),
+    Map(Map),
+    Set(Set),
+}
+
+impl HeapObject {
+    /// Approximate number of bytes owned by the object, including its
+    /// heap-allocated payload.
+    pub fn size(&self) -> usize {
+        use HeapObject::*;
+        let payload = match self {
+            String(s) => s.capacity(),
+            Function(f) => {
+                f.chunk.size()
+                    + f.constants.len() * size_of::<Value>()
+                    + f.upvalues.len() * size_of::<UpvalueRef>()
+            }
+            Closure(c) => c.upvalues.len() * size_of::<usize>(),
+            Upvalue(_) | BoundMethod(_) => 0,
+            Class(c) => c.name.capacity() + c.methods.len() * 2 * size_of::<usize>(),
+            Instance(i) => i.fields.len() * (size_of::<usize>() + size_of::<Value>()),
+            Native(n) => n.name.capacity(),
+            List(l) => l.items.capacity() * size_of::<Value>(),
+            Map(m) => m.capacity() * (2 * size_of::<Value>() + size_of::<HashKey>()),
+            Set(s) => s.capacity() * (size_of::<Value>() + size_of::<HashKey>()),
+        };
+        size_of::<HeapEntry>() + payload
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct HeapEntry {
+    pub(super) marked: bool,
+    pub(super) object: HeapObject,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    pub bytes_allocated: usize,
+    pub next_gc: usize,
+    pub collections: usize,
+    pub live_objects: usize,
+}
+
+#[derive(Debug)]
+pub struct Heap {
+    pub(super) objects: Vec<Option<HeapEntry>>,
+    pub(super) free: Vec<usize>,
+    pub(super) gray: Vec<usize>,
+    pub(super) strings: HashMap<String, usize>,
+    pub(super) bytes_allocated: usize,
+    pub(super) next_gc: usize,
+    pub(super) collections: usize,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self {
+            objects: Vec::new(),
+            free: Vec::new(),
+            gray: Vec::new(),
+            strings: HashMap::new(),
+            bytes_allocated: 0,
+            next_gc: GC_MIN_THRESHOLD,
+            collections: 0,
+        }
+    }
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    pub fn alloc(&mut self, object: HeapObject) -> usize {
+        self.bytes_allocated += object.size();
+        let entry = Some(HeapEntry {
+            marked: false,
+            object,
+        });
+
+        match self.free.pop() {
+            Some(handle) => {
+                self.objects[handle] = entry;
+                handle
+            }
+            None => {
+                self.objects.push(entry);
+                self.objects.len() - 1
+            }
+        }
+    }
+
+    /// Returns the handle of the string object with the given contents,
+    /// allocating it only if no identical string is alive.
+    pub fn intern(&mut self, string: String) -> usize {
+        if let Some(&handle) = self.strings.get(&string) {
+            return handle;
+        }
+        let handle = self.alloc(HeapObject::String(string.clone()));
+        self.strings.insert(string, handle);
+        handle
+    }
+
+    pub fn find_string(&self, string: &str) -> Option<usize> {
+        self.strings.get(string).copied()
+    }
+
+    pub fn should_collect(&self) -> bool {
+        cfg!(feature = "stress_gc") || self.bytes_allocated > self.next_gc
+    }
+
+    pub fn stats(&self) -> GcStats {
+        GcStats {
+            bytes_allocated: self.bytes_allocated,
+            next_gc: self.next_gc,
+            collections: self.collections,
+            live_objects: self.objects.len() - self.free.len(),
+        }
+    }
+
+    pub fn get(&self, handle: usize) -> &HeapObject {
+        match &self.objects[handle] {
+            Some(entry) => &entry.object,
+            None => unreachable!("object {handle} has been freed"),
+        }
+    }
+
+    pub fn get_mut(&mut self, handle: usize) -> &mut HeapObject {
+        match &mut self.objects[handle] {
+            Some(entry) => &mut entry.object,
+            None => unreachable!("object {handle} has been freed"),
+        }
+    }
+
+    pub fn string(&self, handle: usize) -> &String {
+        match self.get(handle) {
+            HeapObject::String(s) => s,
+            _ => unreachable!("object {handle} is not a string"),
+        }
+    }
+
+    pub fn function(&self, handle: usize) -> &Function {
+        match self.get(handle) {
+            HeapObject::Function(f) => f,
+            _ => unreachable!("object {handle} is not a function"),
+        }
+    }
+
+    pub fn closure(&self, handle: usize) -> &Closure {
+        match self.get(handle) {
+            HeapObject::Closure(c) => c,
+            _ => unreachable!("object {handle} is not a closure"),
+        }
+    }
+
+    pub fn upvalue(&self, handle: usize) -> &Upvalue {
+        match self.get(handle) {
+            HeapObject::Upvalue(u) => u,
+            _ => unreachable!("object {handle} is not an upvalue"),
+        }
+    }
+
+    pub fn upvalue_mut(&mut self, handle: usize) -> &mut Upvalue {
+        match self.get_mut(handle) {
+            HeapObject::Upvalue(u) => u,
+            _ => unreachable!("object {handle} is not an upvalue"),
+        }
+    }
+
+    pub fn class(&self, handle: usize) -> &Class {
+        match self.get(handle) {
+            HeapObject::Class(c) => c,
+            _ => unreachable!("object {handle} is not a class"),
+        }
+    }
+
+    pub fn class_mut(&mut self, handle: usize) -> &mut Class {
+        match self.get_mut(handle) {
+            HeapObject::Class(c) => c,
+            _ => unreachable!("object {handle} is not a class"),
+        }
+    }
+
+    pub fn instance(&self, handle: usize) -> &Instance {
+        match self.get(handle) {
+            HeapObject::Instance(i) => i,
+            _ => unreachable!("object {handle} is not an instance"),
+        }
+    }
+
+    pub fn instance_mut(&mut self, handle: usize) -> &mut Instance {
+        match self.get_mut(handle) {
+            HeapObject::Instance(i) => i,
+            _ => unreachable!("object {handle} is not an instance"),
+        }
+    }
+
+    pub fn bound_method(&self, handle: usize) -> &BoundMethod {
+        match self.get(handle) {
+            HeapObject::BoundMethod(b) => b,
+            _ => unreachable!("object {handle} is not a bound method"),
+        }
+    }
+
+    pub fn native(&self, handle: usize) -> &Native {
+        match self.get(handle) {
+            HeapObject::Native(n) => n,
+            _ => unreachable!("object {handle} is not a native function"),
+        }
+    }
+
+    pub fn list(&self, handle: usize) -> &List {
+        match self.get(handle) {
+            HeapObject::List(l) => l,
+            _ => unreachable!("object {handle} is not a list"),
+        }
+    }
+
+    pub fn list_mut(&mut self, handle: usize) -> &mut List {
+        match self.get_mut(handle) {
+            HeapObject::List(l) => l,
+            _ => unreachable!("object {handle} is not a list"),
+        }
+    }
+
+    pub fn map(&self, handle: usize) -> &Map {
+        match self.get(handle) {
+            HeapObject::Map(m) => m,
+            _ => unreachable!("object {handle} is not a map"),
+        }
+    }
+
+    pub fn map_mut(&mut self, handle: usize) -> &mut Map {
+        match self.get_mut(handle) {
+            HeapObject::Map(m) => m,
+            _ => unreachable!("object {handle} is not a map"),
+        }
+    }
+
+    pub fn set(&self, handle: usize) -> &Set {
+        match self.get(handle) {
+            HeapObject::Set(s) => s,
+            _ => unreachable!("object {handle} is not a set"),
+        }
+    }
+
+    pub fn set_mut(&mut self, handle: usize) -> &mut Set {
+        match self.get_mut(handle) {
+            HeapObject::Set(s) => s,
+            _ => unreachable!("object {handle} is not a set"),
+        }
+    }
+}
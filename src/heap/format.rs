@@ -0,0 +1,117 @@
+use std::collections::HashSet;
+
+use crate::value::*;
+
+use super::Heap;
+
+impl Heap {
+    /// Renders a value the way `print` shows it.
+    pub fn format_value(&self, value: &Value) -> String {
+        self.format_nested(value, &mut HashSet::new())
+    }
+
+    /// `open` holds the lists and maps currently being printed, so a
+    /// container that contains itself is shown as `[...]` or `{...}`.
+    fn format_nested(&self, value: &Value, open: &mut HashSet<usize>) -> String {
+        use Value::*;
+        match value {
+            // Keep the `.0` so an integral float still reads as a float.
+            Float(f) if f.fract() == 0.0 => format!("{f:.1}"),
+            Float(f) => format!("{f}"),
+            Int(i) => format!("{i}"),
+            Boolean(b) => format!("{b}"),
+            Nil => "nil".to_string(),
+            Obj(Object::StringObject(idx)) => self.string(*idx).clone(),
+            Obj(Object::FunctionObject(idx)) => self.format_function(*idx),
+            Obj(Object::ClosureObject(idx)) => self.format_function(self.closure(*idx).function),
+            Obj(Object::ClassObject(idx)) => self.class(*idx).name.clone(),
+            Obj(Object::InstanceObject(idx)) => {
+                let class = self.instance(*idx).class;
+                format!("{name} instance", name = self.class(class).name)
+            }
+            Obj(Object::BoundMethodObject(idx)) => {
+                let method = self.bound_method(*idx).method;
+                self.format_function(self.closure(method).function)
+            }
+            Obj(Object::NativeObject(_)) => "<native fn>".to_string(),
+            Obj(Object::ListObject(idx)) if open.contains(idx) => "[...]".to_string(),
+            Obj(Object::ListObject(idx)) => {
+                open.insert(*idx);
+                let items = self.list(*idx).items.iter();
+                let items = items
+                    .map(|item| self.format_nested(item, open))
+                    .collect::<Vec<_>>();
+                open.remove(idx);
+                format!("[{items}]", items = items.join(", "))
+            }
+            Obj(Object::SetObject(idx)) => {
+                let members = self.set(*idx).members();
+                let members = members.map(|member| self.format_nested(member, open));
+                format!(
+                    "Set([{members}])",
+                    members = members.collect::<Vec<_>>().join(", ")
+                )
+            }
+            Obj(Object::MapObject(idx)) if open.contains(idx) => "{...}".to_string(),
+            Obj(Object::MapObject(idx)) => {
+                open.insert(*idx);
+                let entries = self.map(*idx).entries().map(|(key, value)| {
+                    format!(
+                        "{key}: {value}",
+                        key = self.format_nested(key, open),
+                        value = self.format_nested(value, open)
+                    )
+                });
+                let entries = entries.collect::<Vec<_>>().join(", ");
+                open.remove(idx);
+                format!("{{{entries}}}")
+            }
+        }
+    }
+
+    fn format_function(&self, function: usize) -> String {
+        match &self.function(function).name {
+            Some(name) => format!("<fn {name}>"),
+            None => "<script>".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::heap::{Heap, HeapObject};
+    use crate::value::*;
+
+    #[test]
+    fn self_referencing_containers_print_an_ellipsis() {
+        let mut heap = Heap::new();
+        let list = heap.alloc(HeapObject::List(List::default()));
+        heap.list_mut(list).items.push(Value::new_list(list));
+        assert_eq!(heap.format_value(&Value::new_list(list)), "[[...]]");
+
+        let map = heap.alloc(HeapObject::Map(Map::default()));
+        let key = Value::new_string(heap.intern("self".to_string()));
+        let hash_key = HashKey::new(key).unwrap();
+        heap.map_mut(map).insert(hash_key, key, Value::new_map(map));
+        assert_eq!(heap.format_value(&Value::new_map(map)), "{self: {...}}");
+    }
+
+    #[test]
+    fn shared_containers_are_not_cycles() {
+        let mut heap = Heap::new();
+        let inner = heap.alloc(HeapObject::List(List::new(vec![Value::Nil])));
+        let items = vec![Value::new_list(inner), Value::new_list(inner)];
+        let outer = heap.alloc(HeapObject::List(List::new(items)));
+        assert_eq!(heap.format_value(&Value::new_list(outer)), "[[nil], [nil]]");
+    }
+
+    #[test]
+    fn integral_floats_keep_their_fraction() {
+        let heap = Heap::new();
+        assert_eq!(heap.format_value(&Value::Float(3.0)), "3.0");
+        assert_eq!(heap.format_value(&Value::Float(-0.0)), "-0.0");
+        assert_eq!(heap.format_value(&Value::Float(2.5)), "2.5");
+        assert_eq!(heap.format_value(&Value::Float(f64::INFINITY)), "inf");
+        assert_eq!(heap.format_value(&Value::Int(3)), "3");
+    }
+}
@@ -0,0 +1,114 @@
+use crate::value::*;
+
+use super::core::{Heap, HeapObject, GC_HEAP_GROW_FACTOR, GC_MIN_THRESHOLD};
+
+impl Heap {
+    pub fn mark_value(&mut self, value: Value) {
+        if let Some(handle) = value.handle() {
+            self.mark_object(handle);
+        }
+    }
+
+    pub fn mark_object(&mut self, handle: usize) {
+        if let Some(entry) = &mut self.objects[handle] {
+            if entry.marked {
+                return;
+            }
+            entry.marked = true;
+            self.gray.push(handle);
+        }
+    }
+
+    /// Traces from the already marked roots, frees every unreachable object
+    /// and schedules the next collection.
+    pub fn collect(&mut self) {
+        #[cfg(feature = "tracing")]
+        let before = self.bytes_allocated;
+
+        self.trace_references();
+        self.remove_white_strings();
+        self.sweep();
+        self.collections += 1;
+        self.next_gc = (self.bytes_allocated * GC_HEAP_GROW_FACTOR).max(GC_MIN_THRESHOLD);
+
+        #[cfg(feature = "tracing")]
+        println!(
+            "==gc== collected {freed} bytes (from {before} to {after}) next at {next}",
+            freed = before.saturating_sub(self.bytes_allocated),
+            after = self.bytes_allocated,
+            next = self.next_gc
+        );
+    }
+
+    fn trace_references(&mut self) {
+        let mut children = Vec::new();
+        while let Some(handle) = self.gray.pop() {
+            self.references(handle, &mut children);
+            for child in children.drain(..) {
+                self.mark_object(child);
+            }
+        }
+    }
+
+    fn references(&self, handle: usize, children: &mut Vec<usize>) {
+        match self.get(handle) {
+            HeapObject::String(_) | HeapObject::Native(_) => {}
+            HeapObject::Function(f) => {
+                children.extend(f.constants.iter().filter_map(Value::handle))
+            }
+            HeapObject::Closure(c) => {
+                children.push(c.function);
+                children.extend(c.upvalues.iter().copied());
+            }
+            HeapObject::Upvalue(Upvalue::Closed(value)) => children.extend(value.handle()),
+            HeapObject::Upvalue(Upvalue::Open(_)) => {}
+            HeapObject::Class(c) => {
+                children.extend(c.methods.iter().flat_map(|(&name, &method)| [name, method]))
+            }
+            HeapObject::Instance(i) => {
+                children.push(i.class);
+                for (&name, value) in i.fields.iter() {
+                    children.push(name);
+                    children.extend(value.handle());
+                }
+            }
+            HeapObject::List(l) => children.extend(l.items.iter().filter_map(Value::handle)),
+            HeapObject::Set(s) => children.extend(s.members().filter_map(Value::handle)),
+            HeapObject::Map(m) => children.extend(
+                m.entries()
+                    .flat_map(|(key, value)| [key.handle(), value.handle()])
+                    .flatten(),
+            ),
+            HeapObject::BoundMethod(b) => {
+                children.extend(b.receiver.handle());
+                children.push(b.method);
+            }
+        }
+    }
+
+    /// The intern table holds weak references, so drop entries for strings
+    /// that are about to be freed.
+    fn remove_white_strings(&mut self) {
+        let objects = &self.objects;
+        self.strings
+            .retain(|_, &mut handle| matches!(&objects[handle], Some(entry) if entry.marked));
+    }
+
+    fn sweep(&mut self) {
+        let mut bytes_allocated = 0;
+        for (handle, slot) in self.objects.iter_mut().enumerate() {
+            match slot {
+                Some(entry) if entry.marked => {
+                    entry.marked = false;
+                    bytes_allocated += entry.object.size();
+                }
+                Some(_) => {
+                    *slot = None;
+                    self.free.push(handle);
+                }
+                None => {}
+            }
+        }
+        self.bytes_allocated = bytes_allocated;
+    }
+}
@@ -0,0 +1,5 @@
+pub mod core;
+pub mod format;
+pub mod gc;
+
+pub use self::core::*;
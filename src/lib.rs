@@ -0,0 +1,13 @@
+#![allow(dead_code)]
+#![allow(unused_variables)]
+
+pub mod compiler;
+pub mod constants;
+pub mod disassemble;
+pub mod heap;
+pub mod program;
+pub mod stack;
+pub mod token;
+pub mod tokenizer;
+pub mod value;
+pub mod vm;
@@ -1,39 +1,102 @@
-#![allow(dead_code)]
-#![allow(unused_variables)]
-
-mod compiler;
-mod constants;
-mod disassemble;
-mod program;
-mod stack;
-mod token;
-mod tokenizer;
-mod value;
-mod vm;
-use std::time::Instant;
-
-use compiler::Compiler;
-use tokenizer::Tokenizer;
-use vm::VM;
-
-fn main() -> Result<(), ()> {
-    let source_code = include_str!("../numbers.rlox");
-    let chars = source_code.chars().into_iter().collect::<Vec<_>>();
+mod repl;
+
+use std::io::Read;
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rlox::compiler::Compiler;
+use rlox::heap::Heap;
+use rlox::tokenizer::Tokenizer;
+use rlox::value::Value;
+use rlox::vm::{InterpretError, VM};
+
+// Exit codes from sysexits.h.
+const EX_USAGE: u8 = 64;
+const EX_DATAERR: u8 = 65;
+const EX_NOINPUT: u8 = 66;
+const EX_SOFTWARE: u8 = 70;
+
+const USAGE: &str =
+    "Usage: rlox\n       rlox [script | -] [args...]\n       rlox -e <source> [args...]";
+
+fn clock(_vm: &mut VM, _args: &[Value]) -> Result<Value, InterpretError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| InterpretError::runtime_error("System clock is before the epoch."))?;
+    Ok(Value::Float(now.as_secs_f64()))
+}
+
+fn define_globals(vm: &mut VM, args: &[String]) {
+    vm.define_native("clock", 0, clock);
+    vm.define_args(args);
+}
+
+fn run(source: &str, args: &[String]) -> ExitCode {
+    let chars = source.chars().collect::<Vec<_>>();
     let tokenizer = Tokenizer::new(&chars);
-    let mut compiler = Compiler::new(&chars, tokenizer);
-    compiler.compile();
-
-    if !compiler.had_error {
-        let vm = VM::new(compiler.program, compiler.constants);
-        let t = Instant::now();
-        for ir in vm {}
-        println!("{t:?}", t = (Instant::now() - t));
-        return Ok(());
+    let mut heap = Heap::new();
+    let mut compiler = Compiler::new(&chars, tokenizer, &mut heap);
+    let script = compiler.compile();
+
+    if compiler.had_error {
+        for err in compiler.errors.iter() {
+            eprintln!("{err}");
+        }
+        return ExitCode::from(EX_DATAERR);
+    }
+
+    let mut vm = VM::new(script, heap);
+    define_globals(&mut vm, args);
+    match vm.run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            eprint!("{trace}", trace = vm.backtrace());
+            ExitCode::from(EX_SOFTWARE)
+        }
+    }
+}
+
+fn read_source(path: &str) -> Result<String, String> {
+    let mut source = String::new();
+    let read = if path == "-" {
+        std::io::stdin().read_to_string(&mut source)
     } else {
-        println!("Error compiling program");
-        for err in compiler.errors {
-            println!("")
+        std::fs::File::open(path).and_then(|mut file| file.read_to_string(&mut source))
+    };
+    match read {
+        Ok(_) => Ok(source),
+        Err(err) => Err(format!("Could not read '{path}': {err}")),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    match args.first().map(String::as_str) {
+        Some("-e") => match args.get(1) {
+            Some(source) => run(source, &args[2..]),
+            None => {
+                eprintln!("{USAGE}");
+                ExitCode::from(EX_USAGE)
+            }
+        },
+        Some("-h" | "--help") => {
+            println!("{USAGE}");
+            ExitCode::SUCCESS
+        }
+        Some(path) => match read_source(path) {
+            Ok(source) => run(&source, &args[1..]),
+            Err(err) => {
+                eprintln!("{err}");
+                ExitCode::from(EX_NOINPUT)
+            }
+        },
+        None => {
+            let mut vm = VM::with_heap(Heap::new());
+            define_globals(&mut vm, &[]);
+            repl::run(&mut vm);
+            ExitCode::SUCCESS
         }
-        return Err(());
     }
 }
@@ -1,19 +1,94 @@
-#[derive(Debug, Clone, Copy)]
-pub enum OpCode {
+/// Declares `OpCode` together with its byte encoding, so each variant's
+/// opcode byte and operand layout come from a single list. Variants are
+/// numbered in declaration order.
+macro_rules! opcodes {
+    ($($name:ident $(($($operand:ident: $kind:ident),+))?,)*) => {
+        #[derive(Debug, Clone, Copy)]
+        #[allow(clippy::enum_variant_names)]
+        pub enum OpCode {
+            $($name $(($(opcodes!(@usize $operand)),+))?,)*
+        }
+
+        #[allow(clippy::enum_variant_names)]
+        enum Tag {
+            $($name,)*
+        }
+
+        /// The opcode byte of each variant, usable as a pattern.
+        #[allow(non_upper_case_globals)]
+        mod tag {
+            $(pub const $name: u8 = super::Tag::$name as u8;)*
+        }
+
+        // No opcode byte may collide with the `LONG` prefix.
+        const _: () = assert!([$(Tag::$name as u8,)*].len() <= LONG as usize);
+
+        impl Chunk {
+            pub fn write(&mut self, op: OpCode, line: usize) {
+                match op {
+                    $(OpCode::$name $(($($operand),+))? => {
+                        self.write_operands(
+                            tag::$name,
+                            &[$($((Operand::$kind, $operand)),+)?],
+                            line,
+                        )
+                    })*
+                }
+            }
+
+            /// Decodes the instruction whose opcode is at `offset` and whose
+            /// index operands are `WIDTH` bytes wide.
+            #[inline(always)]
+            fn decode_with<const WIDTH: usize>(&self, offset: usize) -> (OpCode, usize) {
+                let mut at = offset + 1;
+                let op = match self.code[offset] {
+                    $(tag::$name => OpCode::$name $((
+                        $(self.read_operand::<WIDTH>(Operand::$kind, opcodes!(@at at $operand))),+
+                    ))?,)*
+                    tag => unreachable!("invalid opcode {tag}"),
+                };
+                (op, at)
+            }
+        }
+
+        /// Every variant, with each operand set to `operand`.
+        #[cfg(test)]
+        fn every_opcode(operand: usize) -> Vec<OpCode> {
+            vec![$(OpCode::$name $(($(opcodes!(@sample operand $operand)),+))?,)*]
+        }
+    };
+    (@usize $operand:ident) => { usize };
+    (@at $at:ident $operand:ident) => { &mut $at };
+    (@sample $value:ident $operand:ident) => { $value };
+}
+
+opcodes! {
     OpReturn,
+    OpPrint,
+    OpPop,
 
-    //BINARY OPERATIONS
+    // Binary operations
     OpAdd,
     OpNegate,
     OpSubtract,
     OpMultiply,
     OpDivide,
+    OpIntDivide,
+    OpModulo,
+    OpPower,
+    OpBitAnd,
+    OpBitOr,
+    OpBitXor,
+    OpBitNot,
+    OpShiftLeft,
+    OpShiftRight,
 
-    OpConstant(usize),
+    OpConstant(index: Index),
     OpNil,
     OpTrue,
     OpFalse,
     OpNot,
+    OpStringify,
 
     OpEqual,
     OpGreater,
@@ -21,7 +96,241 @@ pub enum OpCode {
     OpLess,
     OpLessEqual,
     OpNotEqual,
+
+    OpDefineGlobal(name: Index),
+    OpGetGlobal(name: Index),
+    OpSetGlobal(name: Index),
+    OpGetLocal(slot: Index),
+    OpSetLocal(slot: Index),
+
+    OpJump(distance: Jump),
+    OpJumpIfFalse(distance: Jump),
+    OpLoop(distance: Jump),
+
+    OpCall(argc: Index),
+    OpClosure(index: Index),
+    OpGetUpvalue(slot: Index),
+    OpSetUpvalue(slot: Index),
+    OpCloseUpvalue,
+
+    OpClass(name: Index),
+    OpMethod(name: Index),
+    OpGetProperty(name: Index),
+    OpSetProperty(name: Index),
+    OpInvoke(name: Index, argc: Index),
+    OpInherit,
+    OpGetSuper(name: Index),
+    OpSuperInvoke(name: Index, argc: Index),
+    OpBuildList(count: Index),
+    OpBuildMap(count: Index),
+    OpGetIndex,
+    OpSetIndex,
+}
+
+/// How an operand is encoded: an `Index` takes one byte, or three after a
+/// `LONG` prefix, and a `Jump` always takes two so it can be patched.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Index,
+    Jump,
 }
 
-pub type Instruction = (OpCode, usize);
-pub type Program = Vec<Instruction>;
+/// Prefix byte that widens the operands of the instruction after it from
+/// one byte to three.
+const LONG: u8 = u8::MAX;
+
+/// Largest operand a (long) instruction can encode.
+pub const OPERAND_MAX: usize = 0xFF_FFFF;
+
+/// Largest distance a jump can cover.
+pub const JUMP_MAX: usize = u16::MAX as usize;
+
+/// The line that the run of bytes starting at `start` was compiled from.
+#[derive(Debug, Clone, Copy)]
+struct LineRun {
+    start: usize,
+    line: usize,
+}
+
+/// Bytecode for one function: each instruction is an opcode byte followed by
+/// its operands, with line numbers kept aside in a run-length encoded table.
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    lines: Vec<LineRun>,
+}
+
+impl Chunk {
+    pub fn len(&self) -> usize {
+        self.code.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.code.is_empty()
+    }
+
+    /// Bytes owned by the chunk, for the garbage collector's accounting.
+    pub fn size(&self) -> usize {
+        self.code.capacity() + self.lines.capacity() * std::mem::size_of::<LineRun>()
+    }
+
+    fn write_operands(&mut self, tag: u8, operands: &[(Operand, usize)], line: usize) {
+        let long = operands
+            .iter()
+            .any(|&(kind, value)| kind == Operand::Index && value > u8::MAX as usize);
+        if long {
+            self.push(LONG, line);
+        }
+        self.push(tag, line);
+        for &(kind, value) in operands {
+            let width = match kind {
+                Operand::Index if long => 3,
+                Operand::Index => 1,
+                Operand::Jump => 2,
+            };
+            assert!(value < 1 << (8 * width), "operand {value} out of range");
+            for &byte in &value.to_le_bytes()[..width] {
+                self.push(byte, line);
+            }
+        }
+    }
+
+    /// Sets the distance of the jump instruction at `offset`.
+    pub fn patch_jump(&mut self, offset: usize, distance: usize) {
+        let bytes = (distance as u16).to_le_bytes();
+        self.code[offset + 1..offset + 3].copy_from_slice(&bytes);
+    }
+
+    fn push(&mut self, byte: u8, line: usize) {
+        if self.lines.last().map(|run| run.line) != Some(line) {
+            self.lines.push(LineRun {
+                start: self.code.len(),
+                line,
+            });
+        }
+        self.code.push(byte);
+    }
+
+    /// The source line of the byte at `offset`.
+    pub fn line(&self, offset: usize) -> usize {
+        let run = self.lines.partition_point(|run| run.start <= offset);
+        self.lines[run.saturating_sub(1)].line
+    }
+
+    /// Decodes the instruction at `offset`, returning it together with the
+    /// offset of the next instruction.
+    #[inline(always)]
+    pub fn decode(&self, offset: usize) -> (OpCode, usize) {
+        match self.code[offset] {
+            LONG => self.decode_long(offset + 1),
+            _ => self.decode_with::<1>(offset),
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn decode_long(&self, offset: usize) -> (OpCode, usize) {
+        self.decode_with::<3>(offset)
+    }
+
+    #[inline(always)]
+    fn read_operand<const WIDTH: usize>(&self, kind: Operand, at: &mut usize) -> usize {
+        let width = match kind {
+            Operand::Index => WIDTH,
+            Operand::Jump => 2,
+        };
+        let mut bytes = [0; 8];
+        bytes[..width].copy_from_slice(&self.code[*at..*at + width]);
+        *at += width;
+        usize::from_le_bytes(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OpCode::*;
+    use super::*;
+
+    #[test]
+    fn operands_above_a_byte_take_the_long_form() {
+        let mut chunk = Chunk::default();
+        chunk.write(OpConstant(255), 1);
+        chunk.write(OpConstant(256), 1);
+        chunk.write(OpInvoke(300, 2), 1);
+        chunk.write(OpInvoke(1, 2), 1);
+        assert_eq!(chunk.len(), 2 + 5 + 8 + 3);
+
+        let (op, next) = chunk.decode(0);
+        assert!(matches!(op, OpConstant(255)));
+        assert_eq!(next, 2);
+        let (op, next) = chunk.decode(next);
+        assert!(matches!(op, OpConstant(256)));
+        assert_eq!(next, 7);
+        let (op, next) = chunk.decode(next);
+        assert!(matches!(op, OpInvoke(300, 2)));
+        assert_eq!(next, 15);
+        let (op, next) = chunk.decode(next);
+        assert!(matches!(op, OpInvoke(1, 2)));
+        assert_eq!(next, chunk.len());
+    }
+
+    #[test]
+    fn every_opcode_round_trips_in_short_and_long_form() {
+        for operand in [0, 255, 256, 0x1234, JUMP_MAX, OPERAND_MAX] {
+            let ops = every_opcode(operand);
+            let mut chunk = Chunk::default();
+            for &op in &ops {
+                match op {
+                    OpJump(_) | OpJumpIfFalse(_) | OpLoop(_) if operand > JUMP_MAX => {}
+                    op => chunk.write(op, 1),
+                }
+            }
+
+            let mut offset = 0;
+            for op in ops {
+                if matches!(op, OpJump(_) | OpJumpIfFalse(_) | OpLoop(_)) && operand > JUMP_MAX {
+                    continue;
+                }
+                let (decoded, next) = chunk.decode(offset);
+                assert_eq!(format!("{decoded:?}"), format!("{op:?}"));
+                offset = next;
+            }
+            assert_eq!(offset, chunk.len());
+        }
+    }
+
+    #[test]
+    fn jumps_are_patched_in_place() {
+        let mut chunk = Chunk::default();
+        chunk.write(OpNil, 1);
+        chunk.write(OpJumpIfFalse(0), 1);
+        chunk.write(OpConstant(300), 1);
+        chunk.write(OpPop, 1);
+        let len = chunk.len();
+
+        chunk.patch_jump(1, JUMP_MAX);
+        assert_eq!(chunk.len(), len);
+        let (op, next) = chunk.decode(1);
+        assert!(matches!(op, OpJumpIfFalse(JUMP_MAX)));
+        assert_eq!(next, 4);
+        assert!(matches!(chunk.decode(next).0, OpConstant(300)));
+    }
+
+    #[test]
+    fn lines_are_found_across_runs() {
+        let mut chunk = Chunk::default();
+        chunk.write(OpNil, 1);
+        chunk.write(OpConstant(1000), 1);
+        chunk.write(OpPop, 2);
+        chunk.write(OpJump(4), 2);
+        chunk.write(OpGetLocal(3), 7);
+        chunk.write(OpReturn, 3);
+
+        let expected = [1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 7, 7, 3];
+        assert_eq!(chunk.len(), expected.len());
+        for (offset, line) in expected.into_iter().enumerate() {
+            assert_eq!(chunk.line(offset), line, "line of byte {offset}");
+        }
+        assert_eq!(chunk.lines.len(), 4);
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::{self, BufRead, Lines, StdinLock, Write};
+
+use rlox::compiler::Compiler;
+use rlox::disassemble::disassemble_function;
+use rlox::tokenizer::{TokenType, Tokenizer};
+use rlox::vm::VM;
+
+const HELP: &str = "\
+:dis    disassemble the last input
+:stack  show the value stack left by a failed run
+:help   show this message
+:quit   leave the REPL";
+
+/// Reads, compiles and runs one input at a time against the same VM, so
+/// globals survive between inputs.
+pub fn run(vm: &mut VM) {
+    let mut lines = io::stdin().lock().lines();
+    let mut last: Option<String> = None;
+
+    while let Some(source) = read_input(&mut lines) {
+        match source.trim() {
+            "" => {}
+            ":quit" | ":q" => break,
+            ":help" => println!("{HELP}"),
+            ":dis" => match &last {
+                Some(listing) => print!("{listing}"),
+                None => println!("Nothing to disassemble yet."),
+            },
+            ":stack" => println!("{stack}", stack = vm.format_stack()),
+            command if command.starts_with(':') => {
+                println!("Unknown command '{command}', try :help.")
+            }
+            _ => {
+                if let Some(listing) = interpret(vm, &source) {
+                    last = Some(listing);
+                }
+            }
+        }
+    }
+}
+
+/// Runs `source` and returns its disassembly, rendered before running since
+/// the collector may free its constants afterwards.
+fn interpret(vm: &mut VM, source: &str) -> Option<String> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let tokenizer = Tokenizer::new(&chars);
+    let mut compiler = Compiler::new(&chars, tokenizer, vm.heap_mut());
+    compiler.print_expressions = true;
+    let script = compiler.compile();
+
+    if compiler.had_error {
+        for err in compiler.errors.iter() {
+            eprintln!("{err}");
+        }
+        return None;
+    }
+
+    let listing = disassemble_function(vm.heap(), &script);
+    vm.load(script);
+    if let Err(err) = vm.run() {
+        eprintln!("{err}");
+        eprint!("{trace}", trace = vm.backtrace());
+    }
+    Some(listing)
+}
+
+/// Prompts for a line, then keeps reading continuation lines while the
+/// input so far has unclosed brackets or an unterminated string.
+fn read_input(lines: &mut Lines<StdinLock>) -> Option<String> {
+    prompt("> ");
+    let mut source = lines.next()?.ok()?;
+    while is_incomplete(&source) {
+        prompt(". ");
+        match lines.next() {
+            Some(Ok(line)) => {
+                source.push('\n');
+                source.push_str(&line);
+            }
+            _ => break,
+        }
+    }
+    Some(source)
+}
+
+fn prompt(prompt: &str) {
+    print!("{prompt}");
+    io::stdout().flush().ok();
+}
+
+fn is_incomplete(source: &str) -> bool {
+    use TokenType::*;
+
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut depth = 0;
+    for token in Tokenizer::new(&chars) {
+        match token {
+            Ok(token) => match token.token_type {
+                LeftParen | LeftBrace | LeftBracket => depth += 1,
+                RightParen | RightBrace | RightBracket => depth -= 1,
+                _ => {}
+            },
+            Err(err) if err.is_unterminated_string() => return true,
+            Err(_) => {}
+        }
+    }
+    depth > 0
+}
@@ -1,16 +1,14 @@
-const STACK_SIZE: usize = 255;
+const STACK_SIZE: usize = 64 * 256;
 use crate::value::*;
 
 pub struct Stack {
     arr: Vec<Value>,
-    sp: usize,
 }
 
 impl Default for Stack {
     fn default() -> Self {
         Self {
             arr: Vec::with_capacity(STACK_SIZE),
-            sp: 0,
         }
     }
 }
@@ -20,34 +18,63 @@ pub enum StackError {
     StackOverflow,
 }
 
-impl std::fmt::Display for Stack {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[")?;
-        for i in 0..self.sp {
-            write!(f, "{val:?}, ", val = self.arr[i])?
-        }
-        write!(f, "]")?;
-        Ok(())
-    }
-}
-
 impl Stack {
     pub fn push(&mut self, value: Value) -> Result<(), StackError> {
-        self.arr[self.sp] = value;
-        self.sp += 1;
-        if self.sp == STACK_SIZE {
+        if self.arr.len() == STACK_SIZE {
             Err(StackError::StackOverflow)
         } else {
+            self.arr.push(value);
             Ok(())
         }
     }
 
     pub fn pop(&mut self) -> Result<Value, StackError> {
-        if self.sp == 0 {
+        self.arr.pop().ok_or(StackError::StackUnderflow)
+    }
+
+    pub fn len(&self) -> usize {
+        self.arr.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arr.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Value> {
+        self.arr.iter()
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.arr.truncate(len);
+    }
+
+    pub fn get(&self, idx: usize) -> Result<Value, StackError> {
+        self.arr.get(idx).copied().ok_or(StackError::StackUnderflow)
+    }
+
+    pub fn set(&mut self, idx: usize, value: Value) -> Result<(), StackError> {
+        match self.arr.get_mut(idx) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => Err(StackError::StackUnderflow),
+        }
+    }
+
+    pub fn last_n(&self, n: usize) -> Result<&[Value], StackError> {
+        if n > self.arr.len() {
+            Err(StackError::StackUnderflow)
+        } else {
+            Ok(&self.arr[(self.arr.len() - n)..])
+        }
+    }
+
+    pub fn peek(&self, distance: usize) -> Result<Value, StackError> {
+        if distance >= self.arr.len() {
             Err(StackError::StackUnderflow)
         } else {
-            self.sp -= 1;
-            Ok(self.arr[self.sp].clone())
+            Ok(self.arr[self.arr.len() - 1 - distance])
         }
     }
 }
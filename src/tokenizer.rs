@@ -6,7 +6,11 @@ pub enum TokenType {
     LeftBrace,
     RightBrace,
 
+    LeftBracket,
+    RightBracket,
+
     Comma,
+    Colon,
     Dot,
     Minus,
     Plus,
@@ -14,6 +18,16 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
+    StarStar,
+    TildeSlash,
+    Percent,
+
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    LessLess,
+    GreaterGreater,
 
     Bang,
     BangEqual,
@@ -26,6 +40,8 @@ pub enum TokenType {
 
     Identifier,
     StringLiteral,
+    RawStringLiteral,
+    Interpolation,
     NumericLiteral,
 
     And,
@@ -65,6 +81,8 @@ impl Token {
     }
 }
 
+const UNTERMINATED_STRING: &str = "Undetermined string literal";
+
 #[derive(Debug, Clone)]
 pub struct TokenErr {
     content: String,
@@ -78,6 +96,9 @@ impl TokenErr {
     pub fn line(&self) -> usize {
         self.line
     }
+    pub fn is_unterminated_string(&self) -> bool {
+        self.content == UNTERMINATED_STRING
+    }
 }
 
 impl TokenErr {
@@ -93,17 +114,29 @@ pub struct Tokenizer<'a> {
     source: &'a [char],
     line: usize,
     removed_chars: usize,
+    /// Open brace depth of each `${` currently being lexed, innermost last.
+    interpolations: Vec<usize>,
+    /// Whether the last token opened an interpolation, so a `}` right after
+    /// it closes an empty `${}`.
+    after_interpolation: bool,
 }
 
 impl<'a> Tokenizer<'a> {
     pub fn new(source: &'a [char]) -> Self {
         Self {
             source,
-            line: 0,
+            line: 1,
             removed_chars: 0,
+            interpolations: Vec::new(),
+            after_interpolation: false,
         }
     }
 
+    /// The line the next token will start on.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
     fn eof(&self) -> bool {
         self.source.is_empty()
     }
@@ -177,17 +210,30 @@ impl<'a> Tokenizer<'a> {
             ')' => Some(self.make_token(RightParen, 1)),
             '{' => Some(self.make_token(LeftBrace, 1)),
             '}' => Some(self.make_token(RightBrace, 1)),
+            '[' => Some(self.make_token(LeftBracket, 1)),
+            ']' => Some(self.make_token(RightBracket, 1)),
             ',' => Some(self.make_token(Comma, 1)),
+            ':' => Some(self.make_token(Colon, 1)),
             '.' => Some(self.make_token(Dot, 1)),
             '-' => Some(self.make_token(Minus, 1)),
             '+' => Some(self.make_token(Plus, 1)),
             ';' => Some(self.make_token(Semicolon, 1)),
             '/' => Some(self.make_token(Slash, 1)),
-            '*' => Some(self.make_token(Star, 1)),
+            '%' => Some(self.make_token(Percent, 1)),
+            '&' => Some(self.make_token(Ampersand, 1)),
+            '|' => Some(self.make_token(Pipe, 1)),
+            '^' => Some(self.make_token(Caret, 1)),
             _ => None,
         };
 
-        if let Some(_) = token {
+        if let (Some(token), Some(depth)) = (token, self.interpolations.last_mut()) {
+            match token.token_type {
+                LeftBrace => *depth += 1,
+                RightBrace => *depth -= 1,
+                _ => {}
+            }
+        }
+        if token.is_some() {
             self.advance();
         }
         token
@@ -213,6 +259,8 @@ impl<'a> Tokenizer<'a> {
             '<' => {
                 if self.peak_match('=') {
                     Some(self.make_token(LessEqual, 2))
+                } else if self.peak_match('<') {
+                    Some(self.make_token(LessLess, 2))
                 } else {
                     Some(self.make_token(Less, 1))
                 }
@@ -220,10 +268,26 @@ impl<'a> Tokenizer<'a> {
             '>' => {
                 if self.peak_match('=') {
                     Some(self.make_token(GreaterEqual, 2))
+                } else if self.peak_match('>') {
+                    Some(self.make_token(GreaterGreater, 2))
                 } else {
                     Some(self.make_token(Greater, 1))
                 }
             }
+            '*' => {
+                if self.peak_match('*') {
+                    Some(self.make_token(StarStar, 2))
+                } else {
+                    Some(self.make_token(Star, 1))
+                }
+            }
+            '~' => {
+                if self.peak_match('/') {
+                    Some(self.make_token(TildeSlash, 2))
+                } else {
+                    Some(self.make_token(Tilde, 1))
+                }
+            }
             _ => None,
         };
         if let Some(ref token) = token {
@@ -232,43 +296,141 @@ impl<'a> Tokenizer<'a> {
         token
     }
 
+    /// Lexes a string segment opened by either a quote or the `}` closing an
+    /// interpolated expression. A segment ending in `${` becomes an
+    /// `Interpolation` token and the expression after it is lexed as usual.
     fn string_literal(&mut self) -> TokenResult {
-        use TokenType::StringLiteral;
-        let mut n = 0;
-        while !self.eof_n(n + 1) && self.peak_n(n + 1) != '"' {
-            if self.peak_n(n + 1) == '\n' {
-                self.line += 1;
+        use TokenType::{Interpolation, StringLiteral};
+        let mut n = 1;
+        let mut escape_err: Option<String> = None;
+        while !self.eof_n(n) && self.peak_n(n) != '"' {
+            match self.peak_n(n) {
+                '\n' => self.line += 1,
+                '$' if !self.eof_n(n + 1) && self.peak_n(n + 1) == '{' => {
+                    let token = match escape_err {
+                        Some(msg) => Err(self.make_error_token(&msg)),
+                        None => Ok(self.make_token(Interpolation, n + 2)),
+                    };
+                    self.interpolations.push(0);
+                    self.advance_n(n + 2);
+                    return token;
+                }
+                '\\' => match escape_sequence(&self.source[n..]) {
+                    Ok((_, len)) => {
+                        n += len;
+                        continue;
+                    }
+                    Err(msg) => {
+                        escape_err.get_or_insert(msg);
+                        n += 1;
+                    }
+                },
+                _ => {}
             }
             n += 1;
         }
-        let token = match self.eof_n(n + 1) {
-            true => Err(self.make_error_token("Undetermined string literal")),
-            false => {
-                n += 1;
-                Ok(self.make_token(StringLiteral, n + 1))
+        let token = match (self.eof_n(n), escape_err) {
+            (true, _) => Err(self.make_error_token(UNTERMINATED_STRING)),
+            (false, Some(msg)) => Err(self.make_error_token(&msg)),
+            (false, None) => Ok(self.make_token(StringLiteral, n + 1)),
+        };
+        self.advance_n((n + 1).min(self.source.len()));
+        token
+    }
+
+    /// `r"..."` keeps every character up to the closing quote, backslashes
+    /// and newlines included.
+    fn raw_string_literal(&mut self) -> TokenResult {
+        use TokenType::RawStringLiteral;
+        let mut n = 2;
+        while !self.eof_n(n) && self.peak_n(n) != '"' {
+            if self.peak_n(n) == '\n' {
+                self.line += 1;
             }
+            n += 1;
+        }
+        let token = match self.eof_n(n) {
+            true => Err(self.make_error_token(UNTERMINATED_STRING)),
+            false => Ok(self.make_token(RawStringLiteral, n + 1)),
         };
-        self.advance_n(n + 1);
+        self.advance_n((n + 1).min(self.source.len()));
         token
     }
 
-    fn numeric_literal(&mut self) -> Token {
+    /// Lexes `123`, `1_000`, `12.5`, `1.5e-3`, `0xFF`, `0b1010` and `0o17`.
+    fn numeric_literal(&mut self) -> TokenResult {
         use TokenType::NumericLiteral;
 
-        let mut n = 0;
-        while !self.eof_n(n + 1) && self.peak_n(n + 1).is_digit(10) {
+        let mut err = None;
+        let radix = match (self.peak(), self.source.get(1)) {
+            ('0', Some('x' | 'X')) => Some((16, "hexadecimal")),
+            ('0', Some('b' | 'B')) => Some((2, "binary")),
+            ('0', Some('o' | 'O')) => Some((8, "octal")),
+            _ => None,
+        };
+
+        let mut n = match radix {
+            Some((radix, name)) => {
+                let end = self.digits(2, radix, &mut err);
+                if end == 2 {
+                    err = Some(format!("Expected digits in {name} literal"));
+                }
+                end
+            }
+            None => {
+                let mut n = self.digits(0, 10, &mut err);
+                if !self.eof_n(n) && self.peak_n(n) == '.' {
+                    let end = self.digits(n + 1, 10, &mut err);
+                    if end == n + 1 {
+                        err.get_or_insert("Expected digits after decimal point".to_string());
+                    }
+                    n = end;
+                }
+                if !self.eof_n(n) && matches!(self.peak_n(n), 'e' | 'E') {
+                    n += 1;
+                    if !self.eof_n(n) && matches!(self.peak_n(n), '+' | '-') {
+                        n += 1;
+                    }
+                    let end = self.digits(n, 10, &mut err);
+                    if end == n {
+                        err.get_or_insert("Expected digits in exponent".to_string());
+                    }
+                    n = end;
+                }
+                n
+            }
+        };
+
+        while !self.eof_n(n) && (self.peak_n(n).is_alphanumeric() || self.peak_n(n) == '_') {
+            err.get_or_insert(format!(
+                "Invalid character '{c}' in numeric literal",
+                c = self.peak_n(n)
+            ));
             n += 1;
         }
 
-        if !self.eof_n(n + 1) && self.peak_n(n + 1) == '.' {
-            n += 1;
-            while !self.eof_n(n + 1) && self.peak_n(n + 1).is_digit(10) {
-                n += 1;
+        let token = match err {
+            Some(msg) => Err(self.make_error_token(&msg)),
+            None => Ok(self.make_token(NumericLiteral, n)),
+        };
+        self.advance_n(n);
+        token
+    }
+
+    /// Skips digits of `radix` and `_` separators from offset `n`, reporting
+    /// separators that are not between two digits.
+    fn digits(&self, mut n: usize, radix: u32, err: &mut Option<String>) -> usize {
+        let start = n;
+        let is_digit = |c: char| c.is_digit(radix);
+        while !self.eof_n(n) && (is_digit(self.peak_n(n)) || self.peak_n(n) == '_') {
+            if self.peak_n(n) == '_'
+                && (n == start || self.eof_n(n + 1) || !is_digit(self.peak_n(n + 1)))
+            {
+                err.get_or_insert("Digit separator '_' must be between digits".to_string());
             }
+            n += 1;
         }
-        let token = self.make_token(NumericLiteral, n + 1);
-        self.advance_n(n + 1);
-        token
+        n
     }
 
     fn identifier(&mut self) -> Token {
@@ -280,7 +442,7 @@ impl<'a> Tokenizer<'a> {
 
         let token = match self.source[0..n].iter().collect::<String>().as_ref() {
             "and" => self.make_token(And, 3),
-            "class" => self.make_token(Class, 4),
+            "class" => self.make_token(Class, 5),
             "else" => self.make_token(Else, 4),
             "if" => self.make_token(If, 2),
             "nil" => self.make_token(Nil, 3),
@@ -307,6 +469,15 @@ impl<'a> Tokenizer<'a> {
             return None;
         }
 
+        if self.peak() == '}' && self.interpolations.last() == Some(&0) {
+            self.interpolations.pop();
+            if self.after_interpolation {
+                let err = self.make_error_token("Expect expression inside '${}'.");
+                return Some(self.string_literal().and(Err(err)));
+            }
+            return Some(self.string_literal());
+        }
+
         if let Some(token) = self.single_char_token() {
             return Some(Ok(token));
         }
@@ -320,10 +491,12 @@ impl<'a> Tokenizer<'a> {
             return Some(token);
         }
 
-        if self.peak().is_digit(10) {
-            let token = Ok(self.numeric_literal());
+        if self.peak() == 'r' && self.peak_match('"') {
+            return Some(self.raw_string_literal());
+        }
 
-            return Some(token);
+        if self.peak().is_ascii_digit() {
+            return Some(self.numeric_literal());
         }
 
         if self.peak().is_alphabetic() {
@@ -335,7 +508,7 @@ impl<'a> Tokenizer<'a> {
             Err(self
                 .make_error_token(format!("Unrecognised token {c}", c = self.source[0]).as_ref()));
         self.advance();
-        return Some(unrecognised_token);
+        Some(unrecognised_token)
     }
 
     fn make_token(&self, token_type: TokenType, len: usize) -> Token {
@@ -351,11 +524,66 @@ impl<'a> Tokenizer<'a> {
     }
 }
 
+/// Decodes the escape sequence at the start of `chars` (which begins with the
+/// backslash), returning the character and how many source characters it spans.
+pub fn escape_sequence(chars: &[char]) -> Result<(char, usize), String> {
+    let escaped = match chars.get(1) {
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some('\\') => '\\',
+        Some('"') => '"',
+        Some('$') => '$',
+        Some('u') => return unicode_escape(chars),
+        Some(c) => return Err(format!("Invalid escape sequence '\\{c}'")),
+        None => return Err("Unterminated escape sequence".to_string()),
+    };
+    Ok((escaped, 2))
+}
+
+fn unicode_escape(chars: &[char]) -> Result<(char, usize), String> {
+    let invalid = || "Invalid unicode escape sequence, expected '\\u{...}'".to_string();
+    if chars.get(2) != Some(&'{') {
+        return Err(invalid());
+    }
+    let digits = chars[3..]
+        .iter()
+        .take_while(|c| c.is_ascii_hexdigit())
+        .collect::<String>();
+    if digits.is_empty() || digits.len() > 6 || chars.get(3 + digits.len()) != Some(&'}') {
+        return Err(invalid());
+    }
+    let code = u32::from_str_radix(&digits, 16).map_err(|_| invalid())?;
+    match char::from_u32(code) {
+        Some(c) => Ok((c, digits.len() + 4)),
+        None => Err(format!("Invalid unicode code point '{digits}'")),
+    }
+}
+
+/// Replaces every escape sequence in the body of a string literal.
+pub fn unescape(chars: &[char]) -> Result<String, String> {
+    let mut string = String::with_capacity(chars.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\\' {
+            let (c, len) = escape_sequence(&chars[i..])?;
+            string.push(c);
+            i += len;
+        } else {
+            string.push(chars[i]);
+            i += 1;
+        }
+    }
+    Ok(string)
+}
+
 impl<'a> Iterator for Tokenizer<'a> {
     type Item = TokenResult;
 
     fn next(&mut self) -> Option<Self::Item> {
         let token = self.next_token();
+        self.after_interpolation =
+            matches!(token, Some(Ok(t)) if t.token_type == TokenType::Interpolation);
         #[cfg(feature = "tracing")]
         {
             println!("\n");
@@ -366,3 +594,91 @@ impl<'a> Iterator for Tokenizer<'a> {
         token
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(source: &str) -> Vec<char> {
+        source.chars().collect()
+    }
+
+    #[test]
+    fn simple_escapes_decode() {
+        for (source, expected) in [
+            (r"\n", '\n'),
+            (r"\t", '\t'),
+            (r"\\", '\\'),
+            (r#"\""#, '"'),
+            (r"\$", '$'),
+        ] {
+            assert_eq!(escape_sequence(&chars(source)), Ok((expected, 2)));
+        }
+    }
+
+    #[test]
+    fn unicode_escapes_need_braces_and_a_valid_code_point() {
+        assert_eq!(escape_sequence(&chars(r"\u{41}")), Ok(('A', 6)));
+        assert!(unicode_escape(&chars(r"\u{110000}")).is_err());
+        assert!(unicode_escape(&chars(r"\u41")).is_err());
+    }
+
+    #[test]
+    fn unknown_and_unterminated_escapes_are_rejected() {
+        assert!(escape_sequence(&chars(r"\q")).is_err());
+        assert!(escape_sequence(&chars(r"\")).is_err());
+        assert!(unescape(&chars(r"a\")).is_err());
+    }
+
+    #[test]
+    fn unescape_replaces_every_sequence() {
+        assert_eq!(
+            unescape(&chars(r#"a\tb\n\"\u{1F600}\" costs \$5 \\ "#)).as_deref(),
+            Ok("a\tb\n\"\u{1F600}\" costs $5 \\ ")
+        );
+    }
+
+    #[test]
+    fn empty_interpolation_is_an_error() {
+        use TokenType::*;
+
+        let source = chars(r#""a${}b" "a${ x }b""#);
+        let tokens = Tokenizer::new(&source)
+            .map(|token| token.ok().map(|t| t.token_type))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            [
+                Some(Interpolation),
+                None,
+                Some(Interpolation),
+                Some(Identifier),
+                Some(StringLiteral),
+            ]
+        );
+    }
+
+    fn lex_number(source: &str) -> TokenResult {
+        let source = chars(source);
+        let mut tokenizer = Tokenizer::new(&source);
+        let token = tokenizer.next().expect("a token");
+        assert!(tokenizer.next().is_none(), "more than one token");
+        token
+    }
+
+    #[test]
+    fn numeric_literals_are_accepted() {
+        for source in ["0xFF", "0b1010", "0o17", "1_000_000", "1.5e-3"] {
+            let token = lex_number(source).unwrap_or_else(|err| panic!("{source}: {err:?}"));
+            assert_eq!(token.token_type, TokenType::NumericLiteral);
+            assert_eq!(token.length, source.len());
+        }
+    }
+
+    #[test]
+    fn malformed_numeric_literals_are_rejected() {
+        for source in ["12.", "0x", "1__0", "1_", "1e"] {
+            assert!(lex_number(source).is_err(), "{source} was accepted");
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+
+use super::Value;
+
+#[derive(Debug, Clone)]
+pub struct Class {
+    pub name: String,
+    pub methods: HashMap<usize, usize>,
+}
+
+impl Class {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            methods: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub class: usize,
+    pub fields: HashMap<usize, Value>,
+}
+
+impl Instance {
+    pub fn new(class: usize) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: usize,
+}
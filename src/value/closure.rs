@@ -0,0 +1,20 @@
+#[derive(Debug, Clone)]
+pub struct Closure {
+    pub function: usize,
+    pub upvalues: Vec<usize>,
+}
+
+impl Closure {
+    pub fn new(function: usize) -> Self {
+        Self {
+            function,
+            upvalues: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(super::Value),
+}
@@ -2,6 +2,7 @@
 #[repr(C)]
 pub enum Value {
     Float(f64),
+    Int(i64),
     Boolean(bool),
     Nil,
     Obj(Object),
@@ -12,6 +13,49 @@ impl Value {
         Value::Obj(Object::StringObject(pointer))
     }
 
+    pub fn new_function(pointer: usize) -> Self {
+        Value::Obj(Object::FunctionObject(pointer))
+    }
+
+    pub fn new_closure(pointer: usize) -> Self {
+        Value::Obj(Object::ClosureObject(pointer))
+    }
+
+    pub fn new_class(pointer: usize) -> Self {
+        Value::Obj(Object::ClassObject(pointer))
+    }
+
+    pub fn new_instance(pointer: usize) -> Self {
+        Value::Obj(Object::InstanceObject(pointer))
+    }
+
+    pub fn new_bound_method(pointer: usize) -> Self {
+        Value::Obj(Object::BoundMethodObject(pointer))
+    }
+
+    pub fn new_native(pointer: usize) -> Self {
+        Value::Obj(Object::NativeObject(pointer))
+    }
+
+    pub fn new_list(pointer: usize) -> Self {
+        Value::Obj(Object::ListObject(pointer))
+    }
+
+    pub fn new_map(pointer: usize) -> Self {
+        Value::Obj(Object::MapObject(pointer))
+    }
+
+    pub fn new_set(pointer: usize) -> Self {
+        Value::Obj(Object::SetObject(pointer))
+    }
+
+    pub fn handle(&self) -> Option<usize> {
+        match self {
+            Value::Obj(object) => Some(object.handle()),
+            _ => None,
+        }
+    }
+
     pub fn is_string_object(&self) -> bool {
         matches!(self, Value::Obj(Object::StringObject(..)))
     }
@@ -26,6 +70,34 @@ impl Value {
 
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
+#[allow(clippy::enum_variant_names)]
 pub enum Object {
     StringObject(usize),
+    FunctionObject(usize),
+    ClosureObject(usize),
+    ClassObject(usize),
+    InstanceObject(usize),
+    BoundMethodObject(usize),
+    NativeObject(usize),
+    ListObject(usize),
+    MapObject(usize),
+    SetObject(usize),
+}
+
+impl Object {
+    pub fn handle(&self) -> usize {
+        use Object::*;
+        match *self {
+            StringObject(handle)
+            | FunctionObject(handle)
+            | ClosureObject(handle)
+            | ClassObject(handle)
+            | InstanceObject(handle)
+            | BoundMethodObject(handle)
+            | NativeObject(handle)
+            | ListObject(handle)
+            | MapObject(handle)
+            | SetObject(handle) => handle,
+        }
+    }
 }
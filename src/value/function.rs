@@ -0,0 +1,26 @@
+use crate::constants::Constants;
+use crate::program::Chunk;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpvalueRef {
+    pub index: usize,
+    pub is_local: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Function {
+    pub arity: usize,
+    pub chunk: Chunk,
+    pub constants: Constants,
+    pub upvalues: Vec<UpvalueRef>,
+    pub name: Option<String>,
+}
+
+impl Function {
+    pub fn new(name: Option<String>) -> Self {
+        Self {
+            name,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use super::Value;
+
+#[derive(Debug, Clone, Default)]
+pub struct List {
+    pub items: Vec<Value>,
+}
+
+impl List {
+    pub fn new(items: Vec<Value>) -> Self {
+        Self { items }
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use super::{Object, Value};
+
+/// 2^63, the first float above every i64.
+const I64_END: f64 = 9_223_372_036_854_775_808.0;
+
+/// The hashable projection of a `Value`. Strings are interned, so their
+/// handle identifies their contents, and integral floats share the key of
+/// the equal integer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashKey {
+    Nil,
+    Boolean(bool),
+    Int(i64),
+    Number(u64),
+    String(usize),
+}
+
+impl HashKey {
+    pub fn new(value: Value) -> Option<Self> {
+        match value {
+            Value::Nil => Some(HashKey::Nil),
+            Value::Boolean(b) => Some(HashKey::Boolean(b)),
+            Value::Int(i) => Some(HashKey::Int(i)),
+            Value::Float(f) if f.is_nan() => None,
+            // Integral floats in range equal exactly one integer.
+            Value::Float(f) if f.fract() == 0.0 && (-I64_END..I64_END).contains(&f) => {
+                Some(HashKey::Int(f as i64))
+            }
+            Value::Float(f) => Some(HashKey::Number(f.to_bits())),
+            Value::Obj(Object::StringObject(s)) => Some(HashKey::String(s)),
+            Value::Obj(_) => None,
+        }
+    }
+}
+
+/// A hash map that remembers insertion order so printing and iteration are
+/// deterministic. Removed entries leave a tombstone, so removal is O(1);
+/// tombstones are compacted away once they outnumber live entries.
+#[derive(Debug, Clone, Default)]
+pub struct Map {
+    entries: Vec<Option<(Value, Value)>>,
+    index: HashMap<HashKey, usize>,
+}
+
+impl Map {
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Slots allocated for entries, including tombstones.
+    pub fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
+    /// Key-value pairs in insertion order.
+    pub fn entries(&self) -> impl Iterator<Item = &(Value, Value)> {
+        self.entries.iter().flatten()
+    }
+
+    pub fn get(&self, key: HashKey) -> Option<Value> {
+        self.index
+            .get(&key)
+            .and_then(|&i| self.entries[i].map(|(_, value)| value))
+    }
+
+    pub fn contains(&self, key: HashKey) -> bool {
+        self.index.contains_key(&key)
+    }
+
+    pub fn insert(&mut self, key: HashKey, key_value: Value, value: Value) {
+        match self.index.get(&key) {
+            Some(&i) => self.entries[i] = Some((key_value, value)),
+            None => {
+                self.index.insert(key, self.entries.len());
+                self.entries.push(Some((key_value, value)));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: HashKey) -> Option<Value> {
+        let i = self.index.remove(&key)?;
+        let (_, value) = self.entries[i].take()?;
+        if self.entries.len() > 2 * self.index.len() {
+            compact(&mut self.entries, &mut self.index);
+        }
+        Some(value)
+    }
+}
+
+/// Drops the tombstones from `slots` and points `index` at the new positions.
+pub(super) fn compact<T>(slots: &mut Vec<Option<T>>, index: &mut HashMap<HashKey, usize>) {
+    let mut moved = Vec::with_capacity(slots.len());
+    let mut live = 0;
+    for slot in slots.iter() {
+        moved.push(live);
+        live += slot.is_some() as usize;
+    }
+    for i in index.values_mut() {
+        *i = moved[*i];
+    }
+    slots.retain(Option::is_some);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(i: f64) -> HashKey {
+        HashKey::new(Value::Float(i)).unwrap()
+    }
+
+    fn insert(map: &mut Map, i: f64) {
+        map.insert(key(i), Value::Float(i), Value::Float(i * 10.0));
+    }
+
+    fn keys(map: &Map) -> Vec<f64> {
+        map.entries()
+            .map(|&(key, _)| match key {
+                Value::Float(f) => f,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn removal_keeps_insertion_order_across_compaction() {
+        let mut map = Map::default();
+        (0..10).for_each(|i| insert(&mut map, i as f64));
+        for i in [1.0, 3.0, 5.0, 7.0, 9.0, 0.0] {
+            assert_eq!(map.remove(key(i)), Some(Value::Float(i * 10.0)));
+        }
+        assert_eq!(map.remove(key(3.0)), None);
+        insert(&mut map, 3.0);
+
+        assert_eq!(keys(&map), [2.0, 4.0, 6.0, 8.0, 3.0]);
+        assert_eq!(map.len(), 5);
+        for i in [2.0, 4.0, 6.0, 8.0, 3.0] {
+            assert_eq!(map.get(key(i)), Some(Value::Float(i * 10.0)));
+        }
+    }
+}
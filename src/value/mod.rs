@@ -1,4 +1,18 @@
+pub mod class;
+pub mod closure;
 pub mod core;
+pub mod function;
+pub mod list;
+pub mod map;
+pub mod native;
 pub mod ops;
+pub mod set;
 
+pub use self::class::*;
+pub use self::closure::*;
 pub use self::core::*;
+pub use self::function::*;
+pub use self::list::*;
+pub use self::map::*;
+pub use self::native::*;
+pub use self::set::*;
@@ -0,0 +1,29 @@
+use crate::vm::{InterpretError, VM};
+
+use super::Value;
+
+pub type NativeFn = fn(&mut VM, &[Value]) -> Result<Value, InterpretError>;
+
+#[derive(Debug, Clone)]
+pub struct Native {
+    pub name: String,
+    /// Fewest arguments accepted; equal to `arity` unless some are optional.
+    pub min_arity: usize,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+impl Native {
+    pub fn new(name: &str, arity: usize, function: NativeFn) -> Self {
+        Self {
+            name: name.to_owned(),
+            min_arity: arity,
+            arity,
+            function,
+        }
+    }
+
+    pub fn with_min_arity(self, min_arity: usize) -> Self {
+        Self { min_arity, ..self }
+    }
+}
@@ -1,9 +1,10 @@
 use super::core::*;
 use std::cmp::{Ordering, PartialEq, PartialOrd};
-use std::ops::{Add, Div, Mul, Neg, Not, Sub};
+use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Neg, Not, Rem, Shl, Shr, Sub};
 
 pub enum OpErr {
     Internal,
+    Overflow,
 }
 
 pub struct AdditionErr(pub OpErr);
@@ -16,6 +17,13 @@ pub struct DivisionErr {
 }
 pub struct MultiplyErr(pub OpErr);
 
+pub struct ModuloErr {
+    pub op_err: Option<OpErr>,
+    pub divide_by_zero: bool,
+}
+pub struct PowerErr(pub OpErr);
+pub struct BitwiseErr(pub OpErr);
+
 impl From<OpErr> for AdditionErr {
     fn from(value: OpErr) -> Self {
         Self(value)
@@ -57,14 +65,119 @@ impl From<OpErr> for NegErr {
     }
 }
 
+impl From<OpErr> for ModuloErr {
+    fn from(value: OpErr) -> Self {
+        Self {
+            op_err: Some(value),
+            divide_by_zero: false,
+        }
+    }
+}
+impl ModuloErr {
+    fn divide_by_zero_err() -> Self {
+        Self {
+            op_err: None,
+            divide_by_zero: true,
+        }
+    }
+}
+
+impl From<OpErr> for PowerErr {
+    fn from(value: OpErr) -> Self {
+        Self(value)
+    }
+}
+
+impl From<OpErr> for BitwiseErr {
+    fn from(value: OpErr) -> Self {
+        Self(value)
+    }
+}
+
+impl Value {
+    /// Both operands as floats, if both are numbers.
+    fn as_floats(self, rhs: Value) -> Option<(f64, f64)> {
+        use Value::*;
+        match (self, rhs) {
+            (Float(s), Float(v)) => Some((s, v)),
+            (Int(s), Float(v)) => Some((s as f64, v)),
+            (Float(s), Int(v)) => Some((s, v as f64)),
+            (Int(s), Int(v)) => Some((s as f64, v as f64)),
+            _ => None,
+        }
+    }
+
+    /// Floored division; integral when both operands are integers.
+    pub fn int_div(self, rhs: Value) -> Result<Value, DivisionErr> {
+        use OpErr::*;
+        use Value::*;
+        match (self, rhs) {
+            (Int(_), Int(0)) => Err(DivisionErr::divide_by_zero_err()),
+            (Int(s), Int(v)) => match s.checked_div_euclid(v) {
+                Some(q) if v > 0 || s.rem_euclid(v) == 0 => Ok(Int(q)),
+                Some(q) => Ok(Int(q - 1)),
+                None => Err(Overflow)?,
+            },
+            _ => match self.as_floats(rhs) {
+                Some((_, 0.0)) => Err(DivisionErr::divide_by_zero_err()),
+                Some((s, v)) => Ok(Float((s / v).floor())),
+                None => Err(Internal)?,
+            },
+        }
+    }
+}
+
+impl Value {
+    /// Integral when both operands are integers and the exponent is not
+    /// negative.
+    pub fn pow(self, rhs: Value) -> Result<Value, PowerErr> {
+        use OpErr::*;
+        use Value::*;
+        match (self, rhs) {
+            (Int(s), Int(v)) if v >= 0 => {
+                let v = u32::try_from(v).map_err(|_| Overflow)?;
+                Ok(Int(s.checked_pow(v).ok_or(Overflow)?))
+            }
+            _ => match self.as_floats(rhs) {
+                Some((s, v)) => Ok(Float(s.powf(v))),
+                None => Err(Internal)?,
+            },
+        }
+    }
+
+    pub fn bit_not(self) -> Result<Value, BitwiseErr> {
+        match self {
+            Value::Int(i) => Ok(Value::Int(!i)),
+            _ => Err(OpErr::Internal)?,
+        }
+    }
+
+    fn as_ints(self, rhs: Value) -> Result<(i64, i64), OpErr> {
+        match (self, rhs) {
+            (Value::Int(s), Value::Int(v)) => Ok((s, v)),
+            _ => Err(OpErr::Internal),
+        }
+    }
+
+    fn shift_amount(amount: i64) -> Result<u32, OpErr> {
+        match u32::try_from(amount) {
+            Ok(amount) if amount < i64::BITS => Ok(amount),
+            _ => Err(OpErr::Overflow),
+        }
+    }
+}
+
 impl Add<Value> for Value {
     type Output = Result<Self, AdditionErr>;
     fn add(self, rhs: Value) -> Self::Output {
         use OpErr::*;
         use Value::*;
         match (self, rhs) {
-            (Float(s), Float(v)) => Ok(Float(s + v)),
-            _ => Err(Internal)?,
+            (Int(s), Int(v)) => Ok(Int(s.checked_add(v).ok_or(Overflow)?)),
+            _ => match self.as_floats(rhs) {
+                Some((s, v)) => Ok(Float(s + v)),
+                None => Err(Internal)?,
+            },
         }
     }
 }
@@ -74,8 +187,11 @@ impl Sub<Value> for Value {
         use OpErr::*;
         use Value::*;
         match (self, rhs) {
-            (Float(s), Float(v)) => Ok(Float(s - v)),
-            _ => Err(Internal)?,
+            (Int(s), Int(v)) => Ok(Int(s.checked_sub(v).ok_or(Overflow)?)),
+            _ => match self.as_floats(rhs) {
+                Some((s, v)) => Ok(Float(s - v)),
+                None => Err(Internal)?,
+            },
         }
     }
 }
@@ -86,15 +202,15 @@ impl Div<Value> for Value {
         use OpErr::*;
         use Value::*;
 
-        match (self, rhs) {
-            (Float(s), Float(v)) => {
+        match self.as_floats(rhs) {
+            Some((s, v)) => {
                 if v == 0.0 {
                     Err(DivisionErr::divide_by_zero_err())
                 } else {
                     Ok(Float(s / v))
                 }
             }
-            _ => Err(Internal)?,
+            None => Err(Internal)?,
         }
     }
 }
@@ -105,20 +221,94 @@ impl Mul<Value> for Value {
         use OpErr::*;
         use Value::*;
         match (self, rhs) {
-            (Float(s), Float(v)) => Ok(Float(s * v)),
-            _ => Err(Internal)?,
+            (Int(s), Int(v)) => Ok(Int(s.checked_mul(v).ok_or(Overflow)?)),
+            _ => match self.as_floats(rhs) {
+                Some((s, v)) => Ok(Float(s * v)),
+                None => Err(Internal)?,
+            },
+        }
+    }
+}
+
+/// Floored modulo, so the result takes the sign of the divisor like `~/`.
+impl Rem<Value> for Value {
+    type Output = Result<Self, ModuloErr>;
+    fn rem(self, rhs: Value) -> Self::Output {
+        use OpErr::*;
+        use Value::*;
+        match (self, rhs) {
+            (Int(_), Int(0)) => Err(ModuloErr::divide_by_zero_err()),
+            (Int(s), Int(v)) => {
+                let r = s.wrapping_rem(v);
+                if r != 0 && (r < 0) != (v < 0) {
+                    Ok(Int(r + v))
+                } else {
+                    Ok(Int(r))
+                }
+            }
+            _ => match self.as_floats(rhs) {
+                Some((_, 0.0)) => Err(ModuloErr::divide_by_zero_err()),
+                Some((s, v)) => Ok(Float(s - v * (s / v).floor())),
+                None => Err(Internal)?,
+            },
+        }
+    }
+}
+
+impl BitAnd<Value> for Value {
+    type Output = Result<Self, BitwiseErr>;
+    fn bitand(self, rhs: Value) -> Self::Output {
+        let (s, v) = self.as_ints(rhs)?;
+        Ok(Value::Int(s & v))
+    }
+}
+
+impl BitOr<Value> for Value {
+    type Output = Result<Self, BitwiseErr>;
+    fn bitor(self, rhs: Value) -> Self::Output {
+        let (s, v) = self.as_ints(rhs)?;
+        Ok(Value::Int(s | v))
+    }
+}
+
+impl BitXor<Value> for Value {
+    type Output = Result<Self, BitwiseErr>;
+    fn bitxor(self, rhs: Value) -> Self::Output {
+        let (s, v) = self.as_ints(rhs)?;
+        Ok(Value::Int(s ^ v))
+    }
+}
+
+impl Shl<Value> for Value {
+    type Output = Result<Self, BitwiseErr>;
+    fn shl(self, rhs: Value) -> Self::Output {
+        let (s, v) = self.as_ints(rhs)?;
+        let v = Value::shift_amount(v)?;
+        let shifted = s << v;
+        if shifted >> v != s {
+            Err(OpErr::Overflow)?
         }
+        Ok(Value::Int(shifted))
+    }
+}
+
+impl Shr<Value> for Value {
+    type Output = Result<Self, BitwiseErr>;
+    fn shr(self, rhs: Value) -> Self::Output {
+        let (s, v) = self.as_ints(rhs)?;
+        Ok(Value::Int(s >> Value::shift_amount(v)?))
     }
 }
 
 impl Neg for Value {
     type Output = Result<Self, NegErr>;
     fn neg(self) -> Self::Output {
-        use OpErr::Internal;
-        use Value::Float;
+        use OpErr::*;
+        use Value::*;
 
         match self {
             Float(f) => Ok(Float(-f)),
+            Int(i) => Ok(Int(i.checked_neg().ok_or(Overflow)?)),
             _ => Err(Internal)?,
         }
     }
@@ -132,25 +322,44 @@ impl Not for Value {
         match self {
             Boolean(b) => Boolean(!b),
             Nil => Boolean(true),
-            Float(f) => {
-                if f == 0.0 {
-                    Boolean(true)
-                } else {
-                    Boolean(false)
-                }
-            }
             _ => Boolean(false),
         }
     }
 }
 
+/// Orders an integer against a float exactly, without rounding the integer
+/// to the nearest float first.
+fn cmp_int_float(int: i64, float: f64) -> Option<Ordering> {
+    // 2^63, the first float above every i64.
+    const I64_END: f64 = 9_223_372_036_854_775_808.0;
+    if float.is_nan() {
+        None
+    } else if float >= I64_END {
+        Some(Ordering::Less)
+    } else if float < -I64_END {
+        Some(Ordering::Greater)
+    } else {
+        let whole = float.trunc();
+        let by_whole = int.cmp(&(whole as i64));
+        Some(by_whole.then_with(|| whole.total_cmp(&float)))
+    }
+}
+
 impl PartialEq for Value {
     fn eq(&self, rhs: &Value) -> bool {
         use Value::*;
 
-        matches!((self, rhs), (Float(f), Float(h)) if h ==f )
-            || matches!((self, rhs), (Boolean(a), Boolean(b)) if a ==b)
-            || matches!((self, rhs), (Nil, Nil))
+        match (self, rhs) {
+            (Int(a), Int(b)) => a == b,
+            (Float(a), Float(b)) => a == b,
+            (Int(_), Float(_)) | (Float(_), Int(_)) => {
+                self.partial_cmp(rhs) == Some(Ordering::Equal)
+            }
+            (Boolean(a), Boolean(b)) => a == b,
+            (Nil, Nil) => true,
+            (Obj(a), Obj(b)) => a.handle() == b.handle(),
+            _ => false,
+        }
     }
 }
 
@@ -158,8 +367,44 @@ impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         use Value::*;
         match (self, other) {
+            (Int(a), Int(b)) => a.partial_cmp(b),
             (Float(a), Float(b)) => a.partial_cmp(b),
+            (Int(a), Float(b)) => cmp_int_float(*a, *b),
+            (Float(a), Int(b)) => cmp_int_float(*b, *a).map(Ordering::reverse),
             _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::HashKey;
+
+    #[test]
+    fn integers_and_floats_compare_exactly() {
+        let big = Value::Int(9_007_199_254_740_993);
+        let rounded = Value::Float(9_007_199_254_740_992.0);
+        assert!(big != rounded);
+        assert_eq!(big.partial_cmp(&rounded), Some(Ordering::Greater));
+        assert_eq!(rounded.partial_cmp(&big), Some(Ordering::Less));
+        assert_ne!(HashKey::new(big), HashKey::new(rounded));
+
+        assert!(Value::Int(3) == Value::Float(3.0));
+        assert_eq!(HashKey::new(Value::Int(3)), HashKey::new(Value::Float(3.0)));
+        assert_eq!(
+            Value::Int(3).partial_cmp(&Value::Float(3.5)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            Value::Int(-3).partial_cmp(&Value::Float(-3.5)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            Value::Int(i64::MAX).partial_cmp(&Value::Float(9.3e18)),
+            Some(Ordering::Less)
+        );
+        assert!(Value::Int(i64::MIN) == Value::Float(-9_223_372_036_854_775_808.0));
+        assert_eq!(Value::Int(0).partial_cmp(&Value::Float(f64::NAN)), None);
+    }
+}
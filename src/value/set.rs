@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use super::map::compact;
+use super::{HashKey, Value};
+
+/// A hash set with the same key semantics and tombstoned removal as `Map`,
+/// kept in insertion order.
+#[derive(Debug, Clone, Default)]
+pub struct Set {
+    members: Vec<Option<Value>>,
+    index: HashMap<HashKey, usize>,
+}
+
+impl Set {
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Slots allocated for members, including tombstones.
+    pub fn capacity(&self) -> usize {
+        self.members.capacity()
+    }
+
+    /// Members in insertion order.
+    pub fn members(&self) -> impl Iterator<Item = &Value> {
+        self.members.iter().flatten()
+    }
+
+    pub fn contains(&self, key: HashKey) -> bool {
+        self.index.contains_key(&key)
+    }
+
+    pub fn insert(&mut self, key: HashKey, value: Value) {
+        if !self.index.contains_key(&key) {
+            self.index.insert(key, self.members.len());
+            self.members.push(Some(value));
+        }
+    }
+
+    pub fn remove(&mut self, key: HashKey) -> bool {
+        let Some(i) = self.index.remove(&key) else {
+            return false;
+        };
+        self.members[i] = None;
+        if self.members.len() > 2 * self.index.len() {
+            compact(&mut self.members, &mut self.index);
+        }
+        true
+    }
+
+    /// Members paired with their keys, in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (HashKey, Value)> + '_ {
+        self.members()
+            .filter_map(|&value| HashKey::new(value).map(|key| (key, value)))
+    }
+}
@@ -1,18 +1,26 @@
-use crate::constants::*;
 #[cfg(feature = "tracing")]
 use crate::disassemble::*;
+use crate::heap::{GcStats, Heap, HeapObject};
 use crate::program::*;
 use crate::stack::*;
 use crate::value::*;
 use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use super::frame::CallFrame;
+use super::list_methods::list_index;
+use super::set_methods::set_constructor;
+
+const FRAMES_MAX: usize = 64;
 
 #[derive(Default)]
 pub struct VM {
-    program: Program,
-    constants: Constants,
-    strings: Vec<String>,
-    ip: usize,
-    stack: Stack,
+    frames: Vec<CallFrame>,
+    pub(super) heap: Heap,
+    open_upvalues: Vec<usize>,
+    globals: HashMap<usize, Value>,
+    init_string: usize,
+    pub(super) stack: Stack,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -27,7 +35,7 @@ pub struct InterpretError {
     pub error: InterpretErrorType,
 }
 impl InterpretError {
-    fn runtime_error(msg: &str) -> Self {
+    pub fn runtime_error(msg: &str) -> Self {
         Self {
             msg: msg.to_owned(),
             error: InterpretErrorType::Runtime,
@@ -35,45 +43,435 @@ impl InterpretError {
     }
 }
 
+impl std::fmt::Display for InterpretError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{msg}", msg = self.msg)
+    }
+}
+
 pub type InterpretResult = Result<(), InterpretError>;
 
+/// A built-in method of one receiver type, given the receiver's handle, the
+/// method name and the arguments.
+type BuiltinMethod = fn(&mut VM, usize, &str, &[Value]) -> Result<Value, InterpretError>;
+
 impl VM {
-    pub fn new(program: Program, constants: Constants) -> Self {
-        Self {
-            program,
-            constants,
+    pub fn new(script: Function, heap: Heap) -> Self {
+        let mut vm = Self::with_heap(heap);
+        vm.load(script);
+        vm
+    }
+
+    /// A VM with nothing to run yet; scripts are fed to it with `load`, and
+    /// globals persist between them.
+    pub fn with_heap(heap: Heap) -> Self {
+        let mut vm = Self {
+            heap,
             ..Default::default()
+        };
+        vm.init_string = vm.heap.intern("init".to_string());
+        // Allocated without collecting: the heap may hold a compiled script
+        // that nothing roots until `load`.
+        let set = Native::new("Set", 1, set_constructor).with_min_arity(0);
+        let set = vm.heap.alloc(HeapObject::Native(set));
+        let name = vm.heap.intern("Set".to_string());
+        vm.globals.insert(name, Value::new_native(set));
+        vm
+    }
+
+    /// Pushes a call frame for a freshly compiled script, discarding
+    /// whatever a failed run left behind. Nothing is collected here, since
+    /// the objects the compiler allocated are only reachable through
+    /// `script` until it is on the heap.
+    pub fn load(&mut self, script: Function) {
+        self.reset();
+        let function = self.heap.alloc(HeapObject::Function(script));
+        let script = self.heap.alloc(HeapObject::Closure(Closure::new(function)));
+        self.stack.push(Value::new_closure(script)).ok();
+        self.frames.push(CallFrame::new(script, function, 0));
+    }
+
+    /// Discards the frames and stack left behind by a runtime error.
+    pub fn reset(&mut self) {
+        self.frames.clear();
+        self.stack.truncate(0);
+        self.open_upvalues.clear();
+    }
+
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    /// The heap scripts for this VM must be compiled into.
+    pub fn heap_mut(&mut self) -> &mut Heap {
+        &mut self.heap
+    }
+
+    pub fn stack(&self) -> &Stack {
+        &self.stack
+    }
+
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        self.define_native_object(Native::new(name, arity, function));
+    }
+
+    pub fn define_native_object(&mut self, native: Native) {
+        let name = native.name.clone();
+        let native = self.alloc(HeapObject::Native(native));
+        self.stack.push(Value::new_native(native)).ok();
+        let name_handle = self.intern(name);
+        self.globals.insert(name_handle, Value::new_native(native));
+        self.stack.pop().ok();
+    }
+
+    /// Exposes command-line arguments to the script as the global list `args`.
+    pub fn define_args(&mut self, args: &[String]) {
+        let list = self.alloc(HeapObject::List(List::default()));
+        self.stack.push(Value::new_list(list)).ok();
+        let name = self.intern("args".to_owned());
+        self.globals.insert(name, Value::new_list(list));
+        self.stack.pop().ok();
+        for arg in args {
+            let arg = Value::new_string(self.intern(arg.clone()));
+            self.heap.list_mut(list).items.push(arg);
+        }
+    }
+
+    /// Runs until the script returns or a runtime error occurs. On error the
+    /// call frames are left in place so `backtrace` can report them.
+    pub fn run(&mut self) -> InterpretResult {
+        while !self.frames.is_empty() {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// One `[line N] in name` entry per active call frame, innermost first.
+    pub fn backtrace(&self) -> String {
+        let mut trace = String::new();
+        for frame in self.frames.iter().rev() {
+            let function = self.heap.function(frame.function);
+            let line = function.chunk.line(frame.ip.saturating_sub(1));
+            let name = match &function.name {
+                Some(name) => format!("{name}()"),
+                None => "script".to_string(),
+            };
+            trace.push_str(&format!("[line {line}] in {name}\n"));
+        }
+        trace
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
+    }
+
+    pub fn collect_garbage(&mut self) {
+        for value in self.stack.iter() {
+            self.heap.mark_value(*value);
+        }
+        for (name, value) in self.globals.iter() {
+            self.heap.mark_object(*name);
+            self.heap.mark_value(*value);
+        }
+        self.heap.mark_object(self.init_string);
+        for frame in self.frames.iter() {
+            self.heap.mark_object(frame.closure);
+        }
+        for upvalue in self.open_upvalues.iter() {
+            self.heap.mark_object(*upvalue);
+        }
+        self.heap.collect();
+    }
+
+    pub(super) fn alloc(&mut self, object: HeapObject) -> usize {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.heap.alloc(object)
+    }
+
+    pub(super) fn intern(&mut self, string: String) -> usize {
+        match self.heap.find_string(&string) {
+            Some(handle) => handle,
+            None => {
+                if self.heap.should_collect() {
+                    self.collect_garbage();
+                }
+                self.heap.intern(string)
+            }
+        }
+    }
+
+    fn undefined_variable(&self, name: usize) -> InterpretError {
+        InterpretError::runtime_error(&format!(
+            "Undefined variable '{name}'.",
+            name = self.heap.string(name)
+        ))
+    }
+
+    fn undefined_property(&self, name: usize) -> InterpretError {
+        InterpretError::runtime_error(&format!(
+            "Undefined property '{name}'.",
+            name = self.heap.string(name)
+        ))
+    }
+
+    pub fn format_value(&self, value: &Value) -> String {
+        self.heap.format_value(value)
+    }
+
+    /// The value stack, bottom first, rendered like a list.
+    pub fn format_stack(&self) -> String {
+        let values = self.stack.iter().map(|value| self.format_value(value));
+        format!("[{values}]", values = values.collect::<Vec<_>>().join(", "))
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("no active call frame")
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("no active call frame")
+    }
+
+    fn frame_function(&self) -> &Function {
+        self.heap.function(self.frame().function)
+    }
+
+    fn read_constant(&self, idx: usize) -> Value {
+        self.frame_function().constants[idx]
+    }
+
+    fn read_string(&self, idx: usize) -> usize {
+        match self.read_constant(idx).get_string_ref() {
+            Some(s) => s,
+            None => unreachable!("constant {idx} is not a string"),
+        }
+    }
+
+    fn call_value(&mut self, callee: Value, argc: usize) -> InterpretResult {
+        let receiver_slot = self.stack.len() - argc - 1;
+        match callee {
+            Value::Obj(Object::ClosureObject(closure)) => self.call(closure, argc),
+            Value::Obj(Object::BoundMethodObject(bound)) => {
+                let BoundMethod { receiver, method } = *self.heap.bound_method(bound);
+                self.stack.set(receiver_slot, receiver)?;
+                self.call(method, argc)
+            }
+            Value::Obj(Object::ClassObject(class)) => {
+                let instance = self.alloc(HeapObject::Instance(Instance::new(class)));
+                self.stack
+                    .set(receiver_slot, Value::new_instance(instance))?;
+
+                match self.heap.class(class).methods.get(&self.init_string) {
+                    Some(&initializer) => self.call(initializer, argc),
+                    None if argc != 0 => Err(InterpretError::runtime_error(&format!(
+                        "Expected 0 arguments but got {argc}."
+                    ))),
+                    None => Ok(()),
+                }
+            }
+            Value::Obj(Object::NativeObject(native)) => self.call_native(native, argc),
+            _ => Err(InterpretError::runtime_error(
+                "Can only call functions and classes.",
+            )),
+        }
+    }
+
+    fn call(&mut self, closure: usize, argc: usize) -> InterpretResult {
+        let function = self.heap.closure(closure).function;
+        let arity = self.heap.function(function).arity;
+        if argc != arity {
+            return Err(InterpretError::runtime_error(&format!(
+                "Expected {arity} arguments but got {argc}."
+            )));
+        }
+        if self.frames.len() == FRAMES_MAX {
+            return Err(InterpretError::runtime_error("Stack overflow."));
+        }
+
+        let slot = self.stack.len() - argc - 1;
+        self.frames.push(CallFrame::new(closure, function, slot));
+        Ok(())
+    }
+
+    fn call_native(&mut self, native: usize, argc: usize) -> InterpretResult {
+        let Native {
+            min_arity,
+            arity,
+            function,
+            ..
+        } = self.heap.native(native).clone();
+        if argc < min_arity || argc > arity {
+            let expected = match min_arity == arity {
+                true => format!("{arity}"),
+                false => format!("{min_arity} to {arity}"),
+            };
+            return Err(InterpretError::runtime_error(&format!(
+                "Expected {expected} arguments but got {argc}."
+            )));
+        }
+
+        let args = self.stack.last_n(argc)?.to_vec();
+        let result = function(self, &args)?;
+        self.stack.truncate(self.stack.len() - argc - 1);
+        self.stack.push(result)?;
+        Ok(())
+    }
+
+    /// Calls the built-in method `name` of the string, list, map or set
+    /// `receiver`, which sits below the `argc` arguments on the stack.
+    fn invoke_builtin(
+        &mut self,
+        method: BuiltinMethod,
+        receiver: usize,
+        name: usize,
+        argc: usize,
+    ) -> InterpretResult {
+        let name = self.heap.string(name).clone();
+        let args = self.stack.last_n(argc)?.to_vec();
+        let result = method(self, receiver, &name, &args)?;
+        self.stack.truncate(self.stack.len() - argc - 1);
+        self.stack.push(result)?;
+        Ok(())
+    }
+
+    fn invoke(&mut self, name: usize, argc: usize) -> InterpretResult {
+        let instance = match self.stack.peek(argc)? {
+            Value::Obj(Object::InstanceObject(instance)) => instance,
+            Value::Obj(Object::StringObject(string)) => {
+                return self.invoke_builtin(VM::string_method, string, name, argc)
+            }
+            Value::Obj(Object::ListObject(list)) => {
+                return self.invoke_builtin(VM::list_method, list, name, argc)
+            }
+            Value::Obj(Object::MapObject(map)) => {
+                return self.invoke_builtin(VM::map_method, map, name, argc)
+            }
+            Value::Obj(Object::SetObject(set)) => {
+                return self.invoke_builtin(VM::set_method, set, name, argc)
+            }
+            _ => {
+                return Err(InterpretError::runtime_error(
+                    "Only instances have methods.",
+                ))
+            }
+        };
+
+        if let Some(&field) = self.heap.instance(instance).fields.get(&name) {
+            let receiver_slot = self.stack.len() - argc - 1;
+            self.stack.set(receiver_slot, field)?;
+            return self.call_value(field, argc);
+        }
+
+        let class = self.heap.instance(instance).class;
+        self.invoke_from_class(class, name, argc)
+    }
+
+    fn invoke_from_class(&mut self, class: usize, name: usize, argc: usize) -> InterpretResult {
+        match self.heap.class(class).methods.get(&name) {
+            Some(&method) => self.call(method, argc),
+            None => Err(self.undefined_property(name)),
+        }
+    }
+
+    fn bind_method(&mut self, class: usize, name: usize) -> InterpretResult {
+        let Some(&method) = self.heap.class(class).methods.get(&name) else {
+            return Err(self.undefined_property(name));
+        };
+
+        let receiver = self.stack.peek(0)?;
+        let bound = self.alloc(HeapObject::BoundMethod(BoundMethod { receiver, method }));
+        self.stack.pop()?;
+        self.stack.push(Value::new_bound_method(bound))?;
+        Ok(())
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> usize {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|&&u| matches!(self.heap.upvalue(u), Upvalue::Open(s) if *s == slot));
+        if let Some(&upvalue) = existing {
+            return upvalue;
+        }
+
+        let upvalue = self.alloc(HeapObject::Upvalue(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue);
+        upvalue
+    }
+
+    fn close_upvalues(&mut self, last: usize) -> InterpretResult {
+        let mut still_open = Vec::with_capacity(self.open_upvalues.len());
+        for upvalue in std::mem::take(&mut self.open_upvalues) {
+            match *self.heap.upvalue(upvalue) {
+                Upvalue::Open(slot) if slot >= last => {
+                    *self.heap.upvalue_mut(upvalue) = Upvalue::Closed(self.stack.get(slot)?);
+                }
+                _ => still_open.push(upvalue),
+            }
         }
+        self.open_upvalues = still_open;
+        Ok(())
+    }
+
+    fn compare(&self, a: Value, b: Value) -> Option<Ordering> {
+        match (a.get_string_ref(), b.get_string_ref()) {
+            (Some(a), Some(b)) => Some(self.heap.string(a).cmp(self.heap.string(b))),
+            _ => a.partial_cmp(&b),
+        }
+    }
+
+    /// As in clox, only `nil` and `false` are falsey.
+    fn is_falsey(&self, value: Value) -> bool {
+        matches!(value, Value::Nil | Value::Boolean(false))
     }
 
     pub fn step(&mut self) -> InterpretResult {
-        use Object::*;
         use OpCode::*;
         use Value::*;
 
-        let op = self.program[self.ip];
+        let frame = *self.frame();
+        let (op, next) = self.frame_function().chunk.decode(frame.ip);
+        self.frame_mut().ip = next;
 
-        let res = match op.0 {
+        match op {
             OpConstant(idx) => {
-                self.stack.push(self.constants[idx].clone())?;
+                self.stack.push(self.read_constant(idx))?;
             }
             OpReturn => {
-                let constant = self.stack.pop()?;
+                let result = self.stack.pop()?;
+                self.close_upvalues(frame.slot)?;
+                self.frames.pop();
+                self.stack.truncate(frame.slot);
+                if !self.frames.is_empty() {
+                    self.stack.push(result)?;
+                }
+            }
+            OpPrint => {
+                let val = self.stack.pop()?;
+                println!("{}", self.format_value(&val));
+            }
+            OpPop => {
+                self.stack.pop()?;
             }
             OpNegate => {
                 let val = self.stack.pop()?;
                 self.stack.push((-val)?)?;
             }
             OpAdd => {
-                let b = self.stack.pop()?;
-                let a = self.stack.pop()?;
+                let b = self.stack.peek(0)?;
+                let a = self.stack.peek(1)?;
                 if let (Some(a), Some(b)) = (a.get_string_ref(), b.get_string_ref()) {
-                    let mut new_string = self.strings[a].clone();
-                    new_string.extend(self.strings[b].chars());
-                    self.strings.push(new_string);
-                    self.constants
-                        .push(Value::new_string(self.strings.len() - 1));
+                    let mut new_string = self.heap.string(a).clone();
+                    new_string.push_str(self.heap.string(b));
+                    let string = self.intern(new_string);
+                    self.stack.pop()?;
+                    self.stack.pop()?;
+                    self.stack.push(Value::new_string(string))?;
                 } else {
+                    self.stack.pop()?;
+                    self.stack.pop()?;
                     self.stack.push((a + b)?)?;
                 };
             }
@@ -87,6 +485,50 @@ impl VM {
                 let a = self.stack.pop()?;
                 self.stack.push((a / b)?)?;
             }
+            OpIntDivide => {
+                let b = self.stack.pop()?;
+                let a = self.stack.pop()?;
+                self.stack.push(a.int_div(b)?)?;
+            }
+            OpModulo => {
+                let b = self.stack.pop()?;
+                let a = self.stack.pop()?;
+                self.stack.push((a % b)?)?;
+            }
+            OpPower => {
+                let b = self.stack.pop()?;
+                let a = self.stack.pop()?;
+                self.stack.push(a.pow(b)?)?;
+            }
+            OpBitAnd => {
+                let b = self.stack.pop()?;
+                let a = self.stack.pop()?;
+                self.stack.push((a & b)?)?;
+            }
+            OpBitOr => {
+                let b = self.stack.pop()?;
+                let a = self.stack.pop()?;
+                self.stack.push((a | b)?)?;
+            }
+            OpBitXor => {
+                let b = self.stack.pop()?;
+                let a = self.stack.pop()?;
+                self.stack.push((a ^ b)?)?;
+            }
+            OpBitNot => {
+                let val = self.stack.pop()?;
+                self.stack.push(val.bit_not()?)?;
+            }
+            OpShiftLeft => {
+                let b = self.stack.pop()?;
+                let a = self.stack.pop()?;
+                self.stack.push((a << b)?)?;
+            }
+            OpShiftRight => {
+                let b = self.stack.pop()?;
+                let a = self.stack.pop()?;
+                self.stack.push((a >> b)?)?;
+            }
             OpMultiply => {
                 let b = self.stack.pop()?;
                 let a = self.stack.pop()?;
@@ -97,18 +539,21 @@ impl VM {
             OpTrue => self.stack.push(Value::Boolean(true))?,
             OpNot => {
                 let a = self.stack.pop()?;
-                if let Some(a) = a.get_string_ref() {
-                    let b = !(self.strings[a].len() == 0);
-                    self.stack.push(Boolean(b))?;
-                } else {
-                    self.stack.push(!a)?;
+                self.stack.push(Boolean(self.is_falsey(a)))?;
+            }
+            OpStringify => {
+                let val = self.stack.peek(0)?;
+                if !val.is_string_object() {
+                    let string = self.intern(self.format_value(&val));
+                    self.stack.pop()?;
+                    self.stack.push(Value::new_string(string))?;
                 }
             }
             OpGreater | OpGreaterEqual | OpLess | OpLessEqual => {
                 use Ordering::*;
                 let b = self.stack.pop()?;
                 let a = self.stack.pop()?;
-                let res = match (a.partial_cmp(&b), op.0) {
+                let res = match (self.compare(a, b), op) {
                     (None, _) => Err(InterpretError::runtime_error(
                         "Cannot compare the two types",
                     ))?,
@@ -117,35 +562,242 @@ impl VM {
                     | (Some(Greater), OpGreater | OpGreaterEqual) => true,
                     _ => false,
                 };
+                self.stack.push(Boolean(res))?;
             }
             OpEqual => {
                 let b = self.stack.pop()?;
                 let a = self.stack.pop()?;
-
-                if let (Some(a), Some(b)) = (a.get_string_ref(), b.get_string_ref()) {
-                    let res = self.strings[a] == self.strings[b];
-                    self.stack.push(Value::Boolean(res))?;
-                } else {
-                    self.stack.push(Value::Boolean(a == b))?
-                }
+                self.stack.push(Value::Boolean(a == b))?
             }
             OpNotEqual => {
                 let b = self.stack.pop()?;
                 let a = self.stack.pop()?;
                 self.stack.push(Value::Boolean(a != b))?
             }
+            OpDefineGlobal(idx) => {
+                let name = self.read_string(idx);
+                let val = self.stack.pop()?;
+                self.globals.insert(name, val);
+            }
+            OpGetGlobal(idx) => {
+                let name = self.read_string(idx);
+                match self.globals.get(&name) {
+                    Some(val) => self.stack.push(*val)?,
+                    None => Err(self.undefined_variable(name))?,
+                }
+            }
+            OpSetGlobal(idx) => {
+                let name = self.read_string(idx);
+                let val = self.stack.peek(0)?;
+                match self.globals.get_mut(&name) {
+                    Some(slot) => *slot = val,
+                    None => Err(self.undefined_variable(name))?,
+                }
+            }
+            OpGetLocal(slot) => {
+                let val = self.stack.get(frame.slot + slot)?;
+                self.stack.push(val)?;
+            }
+            OpSetLocal(slot) => {
+                let val = self.stack.peek(0)?;
+                self.stack.set(frame.slot + slot, val)?;
+            }
+            OpJump(offset) => self.frame_mut().ip += offset,
+            OpJumpIfFalse(offset) => {
+                if self.is_falsey(self.stack.peek(0)?) {
+                    self.frame_mut().ip += offset;
+                }
+            }
+            OpLoop(offset) => self.frame_mut().ip -= offset,
+            OpCall(argc) => {
+                let callee = self.stack.peek(argc)?;
+                self.call_value(callee, argc)?;
+            }
+            OpClosure(idx) => {
+                let function = match self.read_constant(idx) {
+                    Value::Obj(Object::FunctionObject(function)) => function,
+                    _ => unreachable!("constant {idx} is not a function"),
+                };
+                let mut closure = Closure::new(function);
+                for upvalue in self.heap.function(function).upvalues.clone() {
+                    let captured = if upvalue.is_local {
+                        self.capture_upvalue(frame.slot + upvalue.index)
+                    } else {
+                        self.heap.closure(frame.closure).upvalues[upvalue.index]
+                    };
+                    closure.upvalues.push(captured);
+                }
+                let closure = self.alloc(HeapObject::Closure(closure));
+                self.stack.push(Value::new_closure(closure))?;
+            }
+            OpGetUpvalue(idx) => {
+                let upvalue = self.heap.closure(frame.closure).upvalues[idx];
+                let val = match *self.heap.upvalue(upvalue) {
+                    Upvalue::Open(slot) => self.stack.get(slot)?,
+                    Upvalue::Closed(val) => val,
+                };
+                self.stack.push(val)?;
+            }
+            OpSetUpvalue(idx) => {
+                let upvalue = self.heap.closure(frame.closure).upvalues[idx];
+                let val = self.stack.peek(0)?;
+                match *self.heap.upvalue(upvalue) {
+                    Upvalue::Open(slot) => self.stack.set(slot, val)?,
+                    Upvalue::Closed(_) => *self.heap.upvalue_mut(upvalue) = Upvalue::Closed(val),
+                }
+            }
+            OpCloseUpvalue => {
+                self.close_upvalues(self.stack.len() - 1)?;
+                self.stack.pop()?;
+            }
+            OpClass(idx) => {
+                let name = self.read_string(idx);
+                let name = self.heap.string(name).clone();
+                let class = self.alloc(HeapObject::Class(Class::new(name)));
+                self.stack.push(Value::new_class(class))?;
+            }
+            OpMethod(idx) => {
+                let name = self.read_string(idx);
+                let method = self.stack.peek(0)?;
+                let class = self.stack.peek(1)?;
+                if let (
+                    Value::Obj(Object::ClassObject(class)),
+                    Value::Obj(Object::ClosureObject(method)),
+                ) = (class, method)
+                {
+                    self.heap.class_mut(class).methods.insert(name, method);
+                }
+                self.stack.pop()?;
+            }
+            OpGetProperty(idx) => {
+                let Value::Obj(Object::InstanceObject(instance)) = self.stack.peek(0)? else {
+                    Err(InterpretError::runtime_error(
+                        "Only instances have properties.",
+                    ))?
+                };
+                let name = self.read_string(idx);
+
+                if let Some(&val) = self.heap.instance(instance).fields.get(&name) {
+                    self.stack.pop()?;
+                    self.stack.push(val)?;
+                } else {
+                    let class = self.heap.instance(instance).class;
+                    self.bind_method(class, name)?;
+                }
+            }
+            OpSetProperty(idx) => {
+                let Value::Obj(Object::InstanceObject(instance)) = self.stack.peek(1)? else {
+                    Err(InterpretError::runtime_error("Only instances have fields."))?
+                };
+                let name = self.read_string(idx);
+
+                let val = self.stack.pop()?;
+                self.heap.instance_mut(instance).fields.insert(name, val);
+                self.stack.pop()?;
+                self.stack.push(val)?;
+            }
+            OpInvoke(idx, argc) => {
+                let name = self.read_string(idx);
+                self.invoke(name, argc)?;
+            }
+            OpInherit => {
+                let Value::Obj(Object::ClassObject(superclass)) = self.stack.peek(1)? else {
+                    Err(InterpretError::runtime_error("Superclass must be a class."))?
+                };
+                if let Value::Obj(Object::ClassObject(subclass)) = self.stack.peek(0)? {
+                    let methods = self.heap.class(superclass).methods.clone();
+                    self.heap.class_mut(subclass).methods.extend(methods);
+                }
+                self.stack.pop()?;
+            }
+            OpGetSuper(idx) => {
+                let name = self.read_string(idx);
+                if let Value::Obj(Object::ClassObject(superclass)) = self.stack.pop()? {
+                    self.bind_method(superclass, name)?;
+                }
+            }
+            OpSuperInvoke(idx, argc) => {
+                let name = self.read_string(idx);
+                if let Value::Obj(Object::ClassObject(superclass)) = self.stack.pop()? {
+                    self.invoke_from_class(superclass, name, argc)?;
+                }
+            }
+            OpBuildList(count) => {
+                let items = self.stack.last_n(count)?.to_vec();
+                let list = self.alloc(HeapObject::List(List::new(items)));
+                self.stack.truncate(self.stack.len() - count);
+                self.stack.push(Value::new_list(list))?;
+            }
+            OpBuildMap(count) => {
+                let mut map = Map::default();
+                for pair in self.stack.last_n(2 * count)?.chunks(2) {
+                    map.insert(self.hash_key(pair[0])?, pair[0], pair[1]);
+                }
+                let map = self.alloc(HeapObject::Map(map));
+                self.stack.truncate(self.stack.len() - 2 * count);
+                self.stack.push(Value::new_map(map))?;
+            }
+            OpGetIndex => {
+                let index = self.stack.pop()?;
+                let val = match self.stack.pop()? {
+                    Value::Obj(Object::ListObject(list)) => {
+                        let items = &self.heap.list(list).items;
+                        items[list_index(index, items.len())?]
+                    }
+                    Value::Obj(Object::StringObject(string)) => {
+                        let chars = self.heap.string(string).chars().collect::<Vec<_>>();
+                        let c = chars[list_index(index, chars.len())?];
+                        Value::new_string(self.intern(c.to_string()))
+                    }
+                    Value::Obj(Object::MapObject(map)) => {
+                        match self.heap.map(map).get(self.hash_key(index)?) {
+                            Some(val) => val,
+                            None => Err(InterpretError::runtime_error(&format!(
+                                "Undefined key '{key}'.",
+                                key = self.format_value(&index)
+                            )))?,
+                        }
+                    }
+                    _ => Err(InterpretError::runtime_error(
+                        "Only lists, maps and strings can be indexed.",
+                    ))?,
+                };
+                self.stack.push(val)?;
+            }
+            OpSetIndex => {
+                let val = self.stack.pop()?;
+                let index = self.stack.pop()?;
+                match self.stack.pop()? {
+                    Value::Obj(Object::ListObject(list)) => {
+                        let items = &mut self.heap.list_mut(list).items;
+                        let index = list_index(index, items.len())?;
+                        items[index] = val;
+                    }
+                    Value::Obj(Object::MapObject(map)) => {
+                        let key = self.hash_key(index)?;
+                        self.heap.map_mut(map).insert(key, index, val);
+                    }
+                    _ => Err(InterpretError::runtime_error(
+                        "Only lists and maps support index assignment.",
+                    ))?,
+                }
+                self.stack.push(val)?;
+            }
         };
 
         #[cfg(feature = "tracing")]
         {
+            let function = self.heap.function(frame.function);
             println!("\n");
             println!("==VM==");
-            disassemble_instruction(&op, &self.constants, self.ip, "\t");
-            println!("\t{}", self.stack);
+            println!(
+                "\t{}",
+                disassemble_instruction(&self.heap, function, frame.ip)
+            );
+            println!("\t{}", self.format_stack());
             println!("\n");
         }
 
-        self.ip += 1;
         Ok(())
     }
 }
@@ -153,7 +805,7 @@ impl VM {
 impl Iterator for VM {
     type Item = ();
     fn next(&mut self) -> Option<Self::Item> {
-        if self.ip == self.program.len() {
+        if self.frames.is_empty() {
             return None;
         }
         match self.step() {
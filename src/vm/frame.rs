@@ -0,0 +1,20 @@
+#[derive(Debug, Clone, Copy)]
+pub struct CallFrame {
+    pub closure: usize,
+    /// The closure's function, cached so fetching an instruction costs a
+    /// single heap lookup.
+    pub function: usize,
+    pub ip: usize,
+    pub slot: usize,
+}
+
+impl CallFrame {
+    pub fn new(closure: usize, function: usize, slot: usize) -> Self {
+        Self {
+            closure,
+            function,
+            ip: 0,
+            slot,
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::heap::HeapObject;
+use crate::value::*;
+
+use super::string_methods::{expect_arity, index_arg, slice_index};
+use super::{InterpretError, VM};
+
+impl VM {
+    pub(super) fn list_method(
+        &mut self,
+        list: usize,
+        method: &str,
+        args: &[Value],
+    ) -> Result<Value, InterpretError> {
+        let len = self.heap.list(list).items.len();
+        let result = match method {
+            "len" => {
+                expect_arity(method, args, 0)?;
+                Value::Int(len as i64)
+            }
+            "push" => {
+                expect_arity(method, args, 1)?;
+                self.heap.list_mut(list).items.push(args[0]);
+                Value::Nil
+            }
+            "pop" => {
+                expect_arity(method, args, 0)?;
+                match self.heap.list_mut(list).items.pop() {
+                    Some(value) => value,
+                    None => {
+                        return Err(InterpretError::runtime_error(
+                            "Can't pop from an empty list.",
+                        ))
+                    }
+                }
+            }
+            "insert" => {
+                expect_arity(method, args, 2)?;
+                let index = list_index(args[0], len + 1)?;
+                self.heap.list_mut(list).items.insert(index, args[1]);
+                Value::Nil
+            }
+            "remove" => {
+                expect_arity(method, args, 1)?;
+                let index = list_index(args[0], len)?;
+                self.heap.list_mut(list).items.remove(index)
+            }
+            "slice" => {
+                expect_arity(method, args, 2)?;
+                let start = slice_index(index_arg(method, args[0])?, len);
+                let end = slice_index(index_arg(method, args[1])?, len).max(start);
+                let items = self.heap.list(list).items[start..end].to_vec();
+                let slice = self.alloc(HeapObject::List(List::new(items)));
+                Value::new_list(slice)
+            }
+            "reverse" => {
+                expect_arity(method, args, 0)?;
+                self.heap.list_mut(list).items.reverse();
+                Value::Nil
+            }
+            "contains" => {
+                expect_arity(method, args, 1)?;
+                Value::Boolean(self.heap.list(list).items.contains(&args[0]))
+            }
+            _ => {
+                return Err(InterpretError::runtime_error(&format!(
+                    "Undefined property '{method}'."
+                )))
+            }
+        };
+        Ok(result)
+    }
+}
+
+/// Resolves a subscript against a list of `len` items, counting negative
+/// indices from the end.
+pub(super) fn list_index(index: Value, len: usize) -> Result<usize, InterpretError> {
+    let index = match index {
+        Value::Int(i) => i,
+        Value::Float(f) if f.fract() == 0.0 => f as i64,
+        _ => {
+            return Err(InterpretError::runtime_error(
+                "List index must be an integer.",
+            ))
+        }
+    };
+    let resolved = if index < 0 { index + len as i64 } else { index };
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(InterpretError::runtime_error(&format!(
+            "List index {index} out of bounds for length {len}."
+        )));
+    }
+    Ok(resolved as usize)
+}
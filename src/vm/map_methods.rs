@@ -0,0 +1,98 @@
+use crate::heap::HeapObject;
+use crate::value::*;
+
+use super::string_methods::expect_arity;
+use super::{InterpretError, VM};
+
+impl VM {
+    pub(super) fn map_method(
+        &mut self,
+        map: usize,
+        method: &str,
+        args: &[Value],
+    ) -> Result<Value, InterpretError> {
+        let result = match method {
+            "len" => {
+                expect_arity(method, args, 0)?;
+                Value::Int(self.heap.map(map).len() as i64)
+            }
+            "keys" => {
+                expect_arity(method, args, 0)?;
+                let keys = self.heap.map(map).entries().map(|&(k, _)| k);
+                let list = List::new(keys.collect());
+                Value::new_list(self.alloc(HeapObject::List(list)))
+            }
+            "values" => {
+                expect_arity(method, args, 0)?;
+                let values = self.heap.map(map).entries().map(|&(_, v)| v);
+                let list = List::new(values.collect());
+                Value::new_list(self.alloc(HeapObject::List(list)))
+            }
+            "entries" => {
+                expect_arity(method, args, 0)?;
+                self.map_entries(map)?
+            }
+            "has" => {
+                expect_arity(method, args, 1)?;
+                let key = self.hash_key(args[0])?;
+                Value::Boolean(self.heap.map(map).contains(key))
+            }
+            "remove" => {
+                expect_arity(method, args, 1)?;
+                let key = self.hash_key(args[0])?;
+                self.heap.map_mut(map).remove(key).unwrap_or(Value::Nil)
+            }
+            _ => {
+                return Err(InterpretError::runtime_error(&format!(
+                    "Undefined property '{method}'."
+                )))
+            }
+        };
+        Ok(result)
+    }
+
+    /// Builds a list of `[key, value]` pairs, keeping the outer list on the
+    /// stack while the pairs are allocated.
+    fn map_entries(&mut self, map: usize) -> Result<Value, InterpretError> {
+        let entries = self.alloc(HeapObject::List(List::default()));
+        self.stack.push(Value::new_list(entries))?;
+        for (key, value) in self.heap.map(map).entries().copied().collect::<Vec<_>>() {
+            let pair = self.alloc(HeapObject::List(List::new(vec![key, value])));
+            self.heap
+                .list_mut(entries)
+                .items
+                .push(Value::new_list(pair));
+        }
+        self.stack.pop()?;
+        Ok(Value::new_list(entries))
+    }
+
+    pub(super) fn hash_key(&self, value: Value) -> Result<HashKey, InterpretError> {
+        HashKey::new(value).ok_or_else(|| {
+            InterpretError::runtime_error(&format!(
+                "Unhashable key '{key}' of type {kind}.",
+                key = self.format_value(&value),
+                kind = type_name(value)
+            ))
+        })
+    }
+}
+
+pub(super) fn type_name(value: Value) -> &'static str {
+    match value {
+        Value::Float(_) => "float",
+        Value::Int(_) => "int",
+        Value::Boolean(_) => "boolean",
+        Value::Nil => "nil",
+        Value::Obj(Object::StringObject(_)) => "string",
+        Value::Obj(Object::FunctionObject(_))
+        | Value::Obj(Object::ClosureObject(_))
+        | Value::Obj(Object::BoundMethodObject(_))
+        | Value::Obj(Object::NativeObject(_)) => "function",
+        Value::Obj(Object::ClassObject(_)) => "class",
+        Value::Obj(Object::InstanceObject(_)) => "instance",
+        Value::Obj(Object::ListObject(_)) => "list",
+        Value::Obj(Object::MapObject(_)) => "map",
+        Value::Obj(Object::SetObject(_)) => "set",
+    }
+}
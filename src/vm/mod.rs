@@ -1,5 +1,10 @@
 pub mod core;
+pub mod frame;
+pub mod list_methods;
+pub mod map_methods;
+pub mod set_methods;
 pub mod stack_err;
+pub mod string_methods;
 pub mod value_err;
 
 pub use self::core::*;
@@ -0,0 +1,119 @@
+use crate::heap::HeapObject;
+use crate::value::*;
+
+use super::map_methods::type_name;
+use super::string_methods::expect_arity;
+use super::{InterpretError, InterpretResult, VM};
+
+/// `Set(iterable)` collects the items of a list, the characters of a string,
+/// the keys of a map or the members of another set; `Set()` is empty.
+pub(super) fn set_constructor(vm: &mut VM, args: &[Value]) -> Result<Value, InterpretError> {
+    let set = vm.alloc(HeapObject::Set(Set::default()));
+    if let Some(&iterable) = args.first() {
+        vm.stack.push(Value::new_set(set))?;
+        vm.extend_set(set, iterable)?;
+        vm.stack.pop()?;
+    }
+    Ok(Value::new_set(set))
+}
+
+impl VM {
+    pub(super) fn set_method(
+        &mut self,
+        set: usize,
+        method: &str,
+        args: &[Value],
+    ) -> Result<Value, InterpretError> {
+        let result = match method {
+            "len" => {
+                expect_arity(method, args, 0)?;
+                Value::Int(self.heap.set(set).len() as i64)
+            }
+            "add" => {
+                expect_arity(method, args, 1)?;
+                let key = self.hash_key(args[0])?;
+                self.heap.set_mut(set).insert(key, args[0]);
+                Value::Nil
+            }
+            "remove" => {
+                expect_arity(method, args, 1)?;
+                let key = self.hash_key(args[0])?;
+                Value::Boolean(self.heap.set_mut(set).remove(key))
+            }
+            "has" => {
+                expect_arity(method, args, 1)?;
+                let key = self.hash_key(args[0])?;
+                Value::Boolean(self.heap.set(set).contains(key))
+            }
+            "union" => {
+                expect_arity(method, args, 1)?;
+                let other = self.set_arg(method, args[0])?;
+                let mut union = self.heap.set(set).clone();
+                for (key, value) in self.heap.set(other).iter() {
+                    union.insert(key, value);
+                }
+                Value::new_set(self.alloc(HeapObject::Set(union)))
+            }
+            "intersection" | "difference" => {
+                expect_arity(method, args, 1)?;
+                let other = self.heap.set(self.set_arg(method, args[0])?);
+                let keep = method == "intersection";
+                let mut result = Set::default();
+                for (key, value) in self.heap.set(set).iter() {
+                    if other.contains(key) == keep {
+                        result.insert(key, value);
+                    }
+                }
+                Value::new_set(self.alloc(HeapObject::Set(result)))
+            }
+            _ => {
+                return Err(InterpretError::runtime_error(&format!(
+                    "Undefined property '{method}'."
+                )))
+            }
+        };
+        Ok(result)
+    }
+
+    fn set_arg(&self, method: &str, value: Value) -> Result<usize, InterpretError> {
+        match value {
+            Value::Obj(Object::SetObject(set)) => Ok(set),
+            _ => Err(InterpretError::runtime_error(&format!(
+                "Argument to '{method}' must be a set."
+            ))),
+        }
+    }
+
+    /// Adds every item of `iterable` to `set`, which must already be
+    /// reachable so the strings made from a string's characters survive.
+    fn extend_set(&mut self, set: usize, iterable: Value) -> InterpretResult {
+        let items = match iterable {
+            Value::Obj(Object::ListObject(list)) => self.heap.list(list).items.clone(),
+            Value::Obj(Object::SetObject(other)) => {
+                self.heap.set(other).members().copied().collect()
+            }
+            Value::Obj(Object::MapObject(map)) => {
+                self.heap.map(map).entries().map(|&(k, _)| k).collect()
+            }
+            Value::Obj(Object::StringObject(string)) => {
+                for c in self.heap.string(string).clone().chars() {
+                    let c = Value::new_string(self.intern(c.to_string()));
+                    let key = self.hash_key(c)?;
+                    self.heap.set_mut(set).insert(key, c);
+                }
+                return Ok(());
+            }
+            _ => {
+                return Err(InterpretError::runtime_error(&format!(
+                    "Cannot build a set from a value of type {kind}.",
+                    kind = type_name(iterable)
+                )))
+            }
+        };
+        for item in items {
+            let key = self.hash_key(item)?;
+            self.heap.set_mut(set).insert(key, item);
+        }
+        Ok(())
+    }
+}
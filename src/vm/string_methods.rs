@@ -0,0 +1,184 @@
+use crate::heap::HeapObject;
+use crate::value::*;
+
+use super::{InterpretError, InterpretResult, VM};
+
+/// Longest string, in bytes, that `repeat` will build.
+const REPEAT_MAX: usize = 1 << 30;
+
+impl VM {
+    pub(super) fn string_method(
+        &mut self,
+        string: usize,
+        method: &str,
+        args: &[Value],
+    ) -> Result<Value, InterpretError> {
+        let s = self.heap.string(string).clone();
+        let result = match method {
+            "len" => {
+                expect_arity(method, args, 0)?;
+                Value::Int(s.chars().count() as i64)
+            }
+            "substring" => {
+                expect_arity(method, args, 2)?;
+                let chars = s.chars().collect::<Vec<_>>();
+                let start = index_arg(method, args[0])?;
+                let end = index_arg(method, args[1])?;
+                if start < 0 || start > end || end > chars.len() as i64 {
+                    return Err(InterpretError::runtime_error(&format!(
+                        "Substring range {start}..{end} out of bounds for string of length {len}.",
+                        len = chars.len()
+                    )));
+                }
+                self.new_string(chars[start as usize..end as usize].iter().collect())
+            }
+            "slice" => {
+                expect_arity(method, args, 2)?;
+                let chars = s.chars().collect::<Vec<_>>();
+                let start = slice_index(index_arg(method, args[0])?, chars.len());
+                let end = slice_index(index_arg(method, args[1])?, chars.len()).max(start);
+                self.new_string(chars[start..end].iter().collect())
+            }
+            "index_of" => {
+                expect_arity(method, args, 1)?;
+                let needle = self.string_arg(method, args[0])?;
+                let index = s
+                    .find(needle.as_str())
+                    .map_or(-1, |byte| s[..byte].chars().count() as i64);
+                Value::Int(index)
+            }
+            "split" => {
+                expect_arity(method, args, 1)?;
+                let separator = self.string_arg(method, args[0])?;
+                let parts = if separator.is_empty() {
+                    s.chars().map(String::from).collect::<Vec<_>>()
+                } else {
+                    s.split(separator.as_str()).map(String::from).collect()
+                };
+                self.new_string_list(parts)?
+            }
+            "join" => {
+                expect_arity(method, args, 1)?;
+                let Value::Obj(Object::ListObject(list)) = args[0] else {
+                    return Err(InterpretError::runtime_error(
+                        "Argument to 'join' must be a list.",
+                    ));
+                };
+                let items = self.heap.list(list).items.iter();
+                let items = items
+                    .map(|item| self.format_value(item))
+                    .collect::<Vec<_>>();
+                self.new_string(items.join(&s))
+            }
+            "trim" => {
+                expect_arity(method, args, 0)?;
+                self.new_string(s.trim().to_string())
+            }
+            "upper" => {
+                expect_arity(method, args, 0)?;
+                self.new_string(s.to_uppercase())
+            }
+            "lower" => {
+                expect_arity(method, args, 0)?;
+                self.new_string(s.to_lowercase())
+            }
+            "replace" => {
+                expect_arity(method, args, 2)?;
+                let from = self.string_arg(method, args[0])?;
+                let to = self.string_arg(method, args[1])?;
+                if from.is_empty() {
+                    return Err(InterpretError::runtime_error(
+                        "Cannot replace an empty string.",
+                    ));
+                }
+                self.new_string(s.replace(from.as_str(), &to))
+            }
+            "starts_with" => {
+                expect_arity(method, args, 1)?;
+                let prefix = self.string_arg(method, args[0])?;
+                Value::Boolean(s.starts_with(prefix.as_str()))
+            }
+            "ends_with" => {
+                expect_arity(method, args, 1)?;
+                let suffix = self.string_arg(method, args[0])?;
+                Value::Boolean(s.ends_with(suffix.as_str()))
+            }
+            "repeat" => {
+                expect_arity(method, args, 1)?;
+                let count = index_arg(method, args[0])?;
+                if count < 0 {
+                    return Err(InterpretError::runtime_error(
+                        "Argument to 'repeat' must not be negative.",
+                    ));
+                }
+                match s.len().checked_mul(count as usize) {
+                    Some(len) if len <= REPEAT_MAX => self.new_string(s.repeat(count as usize)),
+                    _ => {
+                        return Err(InterpretError::runtime_error(
+                            "Result of 'repeat' is too long.",
+                        ))
+                    }
+                }
+            }
+            _ => {
+                return Err(InterpretError::runtime_error(&format!(
+                    "Undefined property '{method}'."
+                )))
+            }
+        };
+        Ok(result)
+    }
+
+    fn string_arg(&self, method: &str, value: Value) -> Result<String, InterpretError> {
+        match value.get_string_ref() {
+            Some(s) => Ok(self.heap.string(s).clone()),
+            None => Err(InterpretError::runtime_error(&format!(
+                "Argument to '{method}' must be a string."
+            ))),
+        }
+    }
+
+    fn new_string(&mut self, string: String) -> Value {
+        Value::new_string(self.intern(string))
+    }
+
+    /// The list is kept on the stack while its items are allocated so a
+    /// collection in between cannot free it.
+    fn new_string_list(&mut self, parts: Vec<String>) -> Result<Value, InterpretError> {
+        let list = self.alloc(HeapObject::List(List::default()));
+        self.stack.push(Value::new_list(list))?;
+        for part in parts {
+            let part = self.new_string(part);
+            self.heap.list_mut(list).items.push(part);
+        }
+        self.stack.pop()?;
+        Ok(Value::new_list(list))
+    }
+}
+
+pub(super) fn expect_arity(method: &str, args: &[Value], arity: usize) -> InterpretResult {
+    if args.len() != arity {
+        return Err(InterpretError::runtime_error(&format!(
+            "Expected {arity} arguments but got {argc} in '{method}'.",
+            argc = args.len()
+        )));
+    }
+    Ok(())
+}
+
+pub(super) fn index_arg(method: &str, value: Value) -> Result<i64, InterpretError> {
+    match value {
+        Value::Int(i) => Ok(i),
+        Value::Float(f) if f.fract() == 0.0 => Ok(f as i64),
+        _ => Err(InterpretError::runtime_error(&format!(
+            "Argument to '{method}' must be an integer."
+        ))),
+    }
+}
+
+/// Resolves a possibly negative index against `len`, clamping it into range.
+pub(super) fn slice_index(index: i64, len: usize) -> usize {
+    let len = len as i64;
+    let index = if index < 0 { len + index } else { index };
+    index.clamp(0, len) as usize
+}
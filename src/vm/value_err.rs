@@ -1,15 +1,23 @@
-use crate::value::ops::{AdditionErr, DivisionErr, MultiplyErr, NegErr, SubtractionErr};
+use crate::value::ops::{
+    AdditionErr, BitwiseErr, DivisionErr, ModuloErr, MultiplyErr, NegErr, OpErr, PowerErr,
+    SubtractionErr,
+};
 
 use super::{InterpretError, InterpretErrorType};
 
+fn op_err_msg(err: &OpErr, internal: &str, operation: &str) -> String {
+    match err {
+        OpErr::Internal => internal.to_string(),
+        OpErr::Overflow => format!("Integer overflow in {operation}."),
+    }
+}
+
 impl From<AdditionErr> for InterpretError {
     fn from(value: AdditionErr) -> Self {
         use InterpretErrorType::*;
-        match value.0 {
-            _ => Self {
-                msg: "Addition err".to_string(),
-                error: Runtime,
-            },
+        Self {
+            msg: op_err_msg(&value.0, "Addition err", "addition"),
+            error: Runtime,
         }
     }
 }
@@ -17,11 +25,9 @@ impl From<AdditionErr> for InterpretError {
 impl From<SubtractionErr> for InterpretError {
     fn from(value: SubtractionErr) -> Self {
         use InterpretErrorType::*;
-        match value.0 {
-            _ => Self {
-                msg: "Subtraction err".to_string(),
-                error: Runtime,
-            },
+        Self {
+            msg: op_err_msg(&value.0, "Subtraction err", "subtraction"),
+            error: Runtime,
         }
     }
 }
@@ -29,8 +35,13 @@ impl From<SubtractionErr> for InterpretError {
 impl From<DivisionErr> for InterpretError {
     fn from(value: DivisionErr) -> Self {
         use InterpretErrorType::*;
+        let msg = match value.op_err {
+            _ if value.divide_by_zero => "Division by zero.".to_string(),
+            Some(err) => op_err_msg(&err, "Divison err", "division"),
+            None => "Divison err".to_string(),
+        };
         Self {
-            msg: "Divison err".to_string(),
+            msg,
             error: Runtime,
         }
     }
@@ -39,11 +50,9 @@ impl From<DivisionErr> for InterpretError {
 impl From<MultiplyErr> for InterpretError {
     fn from(value: MultiplyErr) -> Self {
         use InterpretErrorType::*;
-        match value.0 {
-            _ => Self {
-                msg: "Multiply err".to_string(),
-                error: Runtime,
-            },
+        Self {
+            msg: op_err_msg(&value.0, "Multiply err", "multiplication"),
+            error: Runtime,
         }
     }
 }
@@ -51,11 +60,44 @@ impl From<MultiplyErr> for InterpretError {
 impl From<NegErr> for InterpretError {
     fn from(value: NegErr) -> Self {
         use InterpretErrorType::*;
-        match value.0 {
-            _ => Self {
-                msg: "Negation err".to_string(),
-                error: Runtime,
-            },
+        Self {
+            msg: op_err_msg(&value.0, "Negation err", "negation"),
+            error: Runtime,
+        }
+    }
+}
+
+impl From<ModuloErr> for InterpretError {
+    fn from(value: ModuloErr) -> Self {
+        use InterpretErrorType::*;
+        let msg = match value.op_err {
+            _ if value.divide_by_zero => "Modulo by zero.".to_string(),
+            Some(err) => op_err_msg(&err, "Modulo err", "modulo"),
+            None => "Modulo err".to_string(),
+        };
+        Self {
+            msg,
+            error: Runtime,
+        }
+    }
+}
+
+impl From<PowerErr> for InterpretError {
+    fn from(value: PowerErr) -> Self {
+        use InterpretErrorType::*;
+        Self {
+            msg: op_err_msg(&value.0, "Power err", "exponentiation"),
+            error: Runtime,
+        }
+    }
+}
+
+impl From<BitwiseErr> for InterpretError {
+    fn from(value: BitwiseErr) -> Self {
+        use InterpretErrorType::*;
+        Self {
+            msg: op_err_msg(&value.0, "Bitwise operands must be integers.", "shift"),
+            error: Runtime,
         }
     }
 }